@@ -1,8 +1,10 @@
 use crate::errors::AppResult;
-use crate::rules::{protected_top_level_folders, Rules};
+use crate::exclude::ExcludeSet;
+use crate::executor;
+use crate::rules::{protected_top_level_folders, Rules, DUPLICATES_FOLDER};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,7 +27,8 @@ pub fn cleanup_empty_folders(rules: &Rules) -> AppResult<CleanupResult> {
     }
 
     let root = Path::new(&rules.global.sort_root);
-    let protected = protected_top_level_folders(rules);
+    let mut protected = protected_top_level_folders(rules);
+    protected.insert(DUPLICATES_FOLDER.to_string());
     let mut result = CleanupResult {
         trashed: 0,
         skipped: 0,
@@ -33,11 +36,13 @@ pub fn cleanup_empty_folders(rules: &Rules) -> AppResult<CleanupResult> {
         skipped_paths: Vec::new(),
     };
 
-    for entry in WalkDir::new(root)
-        .min_depth(1)
-        .contents_first(true)
-        .into_iter()
-    {
+    // `.sortignore` files load top-down, so folders are collected first and then visited
+    // deepest first, letting a folder emptied by trashing its children go in the same pass.
+    // The planner reports unreadable `.sortignore` lines; the valid ones still apply here.
+    let (mut excludes, _) = ExcludeSet::new(rules, root);
+    let mut folders: Vec<PathBuf> = Vec::new();
+    let mut walker = WalkDir::new(root).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
         let entry = match entry {
             Ok(value) => value,
             Err(err) => {
@@ -57,10 +62,20 @@ pub fn cleanup_empty_folders(rules: &Rules) -> AppResult<CleanupResult> {
             continue;
         }
 
+        if excludes.excluded_by(path, true).is_some() {
+            walker.skip_current_dir();
+            result.skipped += 1;
+            continue;
+        }
+        let _ = excludes.load_dir(path);
+        folders.push(path.to_path_buf());
+    }
+
+    for path in folders.iter().rev() {
         match fs::read_dir(path) {
             Ok(mut dir_entries) => {
                 if dir_entries.next().is_none() {
-                    match trash::delete(path) {
+                    match executor::move_to_trash(path) {
                         Ok(()) => result.trashed += 1,
                         Err(err) => {
                             result.errors += 1;
@@ -0,0 +1,279 @@
+use crate::errors::{AppError, AppResult};
+use crate::executor::{self, MovedFile};
+use crate::hash;
+use crate::journal;
+use crate::planner::{DuplicateMark, PlanSkip};
+use crate::rules::{
+    match_bundle, protected_top_level_folders, DuplicateAction, Rules, DUPLICATES_FOLDER,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+/// Byte-identical files found in the library.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateSet {
+    pub hash: String,
+    /// Size of each copy in bytes.
+    pub size: u64,
+    pub files: Vec<String>,
+    /// Bytes freed by keeping only one copy.
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateReport {
+    pub generated_at: String,
+    pub scanned_files: u64,
+    /// Largest `reclaimable_bytes` first.
+    pub sets: Vec<DuplicateSet>,
+    pub reclaimable_bytes: u64,
+    /// Files and folders that could not be read.
+    pub errors: Vec<PlanSkip>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateTrashDetail {
+    pub path: String,
+    /// `trashed`, `skipped` or `error`.
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateTrashResult {
+    pub session_id: String,
+    pub trashed: u64,
+    pub skipped: u64,
+    pub errors: u64,
+    pub reclaimed_bytes: u64,
+    pub details: Vec<DuplicateTrashDetail>,
+}
+
+/// Walks every category folder (and `Duplicates`) under the sort root, groups files by size
+/// and then by content hash. Bundles and empty files are left out.
+pub fn find_library_duplicates(rules: &Rules) -> AppResult<DuplicateReport> {
+    let sort_root = PathBuf::from(&rules.global.sort_root);
+    let mut folders: Vec<String> = protected_top_level_folders(rules).into_iter().collect();
+    folders.push(DUPLICATES_FOLDER.to_string());
+    folders.sort();
+
+    let mut errors = Vec::new();
+    let mut scanned_files = 0_u64;
+    let mut by_size: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+
+    for folder in folders {
+        let dir = sort_root.join(&folder);
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let mut walker = WalkDir::new(&dir).min_depth(1).into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(value) => value,
+                Err(err) => {
+                    errors.push(PlanSkip {
+                        path: err
+                            .path()
+                            .unwrap_or(&dir)
+                            .to_string_lossy()
+                            .to_string(),
+                        reason: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            if entry.file_type().is_dir() {
+                if match_bundle(rules, entry.path()).is_some() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            scanned_files += 1;
+            match entry.metadata() {
+                Ok(meta) if meta.len() > 0 => {
+                    by_size.entry(meta.len()).or_default().push(entry.into_path());
+                }
+                Ok(_) => {}
+                Err(err) => errors.push(PlanSkip {
+                    path: entry.path().to_string_lossy().to_string(),
+                    reason: err.to_string(),
+                }),
+            }
+        }
+    }
+
+    let mut sets = Vec::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+
+        let mut by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for path in paths {
+            match hash::file_digest(&path) {
+                Ok(digest) => by_hash
+                    .entry(digest)
+                    .or_default()
+                    .push(path.to_string_lossy().to_string()),
+                Err(err) => errors.push(PlanSkip {
+                    path: path.to_string_lossy().to_string(),
+                    reason: err.to_string(),
+                }),
+            }
+        }
+
+        for (digest, mut files) in by_hash {
+            if files.len() < 2 {
+                continue;
+            }
+            files.sort();
+            sets.push(DuplicateSet {
+                hash: digest,
+                size,
+                reclaimable_bytes: size * (files.len() as u64 - 1),
+                files,
+            });
+        }
+    }
+    sets.sort_by(|a, b| {
+        b.reclaimable_bytes
+            .cmp(&a.reclaimable_bytes)
+            .then_with(|| a.files.cmp(&b.files))
+    });
+
+    Ok(DuplicateReport {
+        generated_at: Utc::now().to_rfc3339(),
+        scanned_files,
+        reclaimable_bytes: sets.iter().map(|set| set.reclaimable_bytes).sum(),
+        sets,
+        errors,
+    })
+}
+
+/// Trashes the selected library duplicates and journals each one with the copy that was
+/// kept. The library is rescanned first, so a path is only trashed while an identical copy
+/// still exists; when every copy of a set is selected, the first one is kept. A journal
+/// write error is reported in the result rather than hiding what was already trashed.
+pub fn trash_duplicates(
+    rules: &Rules,
+    journal_path: &Path,
+    paths: &[String],
+) -> AppResult<DuplicateTrashResult> {
+    let report = find_library_duplicates(rules)?;
+    let selected: HashSet<&str> = paths.iter().map(String::as_str).collect();
+
+    let mut result = DuplicateTrashResult {
+        session_id: Uuid::new_v4().to_string(),
+        trashed: 0,
+        skipped: 0,
+        errors: 0,
+        reclaimed_bytes: 0,
+        details: Vec::new(),
+    };
+    let mut journaled = Vec::new();
+    let mut wal = journal::WriteAhead::begin(
+        journal_path,
+        &result.session_id,
+        Some(journal::DUPLICATE_TRASH_RUN_KIND),
+        None,
+    )?;
+
+    for path in paths {
+        let set = report
+            .sets
+            .iter()
+            .find(|set| set.files.iter().any(|file| file == path));
+        let Some(set) = set else {
+            result.skipped += 1;
+            result.details.push(DuplicateTrashDetail {
+                path: path.clone(),
+                status: "skipped".to_string(),
+                message: "no identical copy is left in the library".to_string(),
+            });
+            continue;
+        };
+        let kept = set
+            .files
+            .iter()
+            .find(|file| !selected.contains(file.as_str()))
+            .unwrap_or(&set.files[0]);
+        if kept == path {
+            result.skipped += 1;
+            result.details.push(DuplicateTrashDetail {
+                path: path.clone(),
+                status: "skipped".to_string(),
+                message: "every copy is selected; keeping this one".to_string(),
+            });
+            continue;
+        }
+
+        let entry = MovedFile {
+            source_path: path.clone(),
+            destination_path: String::new(),
+            category: String::new(),
+            collision_renamed: false,
+            bundle: None,
+            collision: None,
+            duplicate: Some(DuplicateMark {
+                original: kept.clone(),
+                action: DuplicateAction::Trash,
+            }),
+        };
+        // A file whose intent cannot be logged is not trashed.
+        let trashed = wal
+            .intent(&entry)
+            .and_then(|()| executor::move_to_trash(Path::new(path)).map_err(AppError::from));
+        match trashed {
+            Ok(()) => {
+                // The file is gone either way; a missing `done` record only makes recovery
+                // check that the source no longer exists.
+                let _ = wal.done(path);
+                result.trashed += 1;
+                result.reclaimed_bytes += set.size;
+                result.details.push(DuplicateTrashDetail {
+                    path: path.clone(),
+                    status: "trashed".to_string(),
+                    message: format!("kept {}", kept),
+                });
+                journaled.push(entry);
+            }
+            Err(err) => {
+                result.errors += 1;
+                result.details.push(DuplicateTrashDetail {
+                    path: path.clone(),
+                    status: "error".to_string(),
+                    message: err.to_string(),
+                });
+            }
+        }
+    }
+
+    // The files are already in the Trash, so report them even if the journal cannot be
+    // written. The write-ahead log is kept then, and startup recovery journals them.
+    match journal::append_duplicate_trash(journal_path, &result.session_id, &journaled) {
+        Ok(()) => wal.finish()?,
+        Err(err) => {
+            result.errors += 1;
+            result.details.push(DuplicateTrashDetail {
+                path: journal_path.to_string_lossy().to_string(),
+                status: "error".to_string(),
+                message: format!("could not journal the trashed files: {}", err),
+            });
+        }
+    }
+    Ok(result)
+}
@@ -16,6 +16,8 @@ pub enum AppError {
     Validation(String),
     #[error("state error: {0}")]
     State(String),
+    #[error("rules.json schema version {found} is newer than this app supports ({supported})")]
+    UnsupportedSchemaVersion { found: u32, supported: u32 },
 }
 
 pub type AppResult<T> = Result<T, AppError>;
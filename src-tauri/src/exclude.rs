@@ -0,0 +1,84 @@
+use crate::rules::Rules;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::{Path, PathBuf};
+
+/// Per-directory ignore file, gitignore syntax, patterns relative to its own directory.
+pub const SORTIGNORE_FILE_NAME: &str = ".sortignore";
+
+/// Gitignore-style exclusions: `global.excludePatterns` plus every `.sortignore` met
+/// while walking the sort root or an inbox folder top-down.
+///
+/// Precedence follows git: the deepest `.sortignore` that has an opinion wins (so `!keep.me`
+/// can re-include), then shallower ones, then the global patterns.
+pub struct ExcludeSet {
+    global: Gitignore,
+    local: Vec<(PathBuf, Gitignore)>,
+}
+
+impl ExcludeSet {
+    /// Builds the global matcher anchored at `root` and loads `root`'s own `.sortignore`.
+    /// Invalid global patterns are dropped here; `validate_rules` reports them.
+    pub fn new(rules: &Rules, root: &Path) -> (Self, Option<String>) {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &rules.global.exclude_patterns {
+            let _ = builder.add_line(None, pattern);
+        }
+        let global = builder.build().unwrap_or_else(|_| Gitignore::empty());
+
+        let mut set = Self {
+            global,
+            local: Vec::new(),
+        };
+        let err = set.load_dir(root);
+        (set, err)
+    }
+
+    /// Loads `dir/.sortignore` if present. Returns a message for unreadable or invalid lines;
+    /// the valid lines still apply.
+    pub fn load_dir(&mut self, dir: &Path) -> Option<String> {
+        let file = dir.join(SORTIGNORE_FILE_NAME);
+        if !file.is_file() {
+            return None;
+        }
+
+        let (matcher, err) = Gitignore::new(&file);
+        if !matcher.is_empty() {
+            self.local.push((dir.to_path_buf(), matcher));
+        }
+        err.map(|err| err.to_string())
+    }
+
+    /// Returns a skip reason naming the matching pattern, or `None` when `path` is not excluded.
+    pub fn excluded_by(&self, path: &Path, is_dir: bool) -> Option<String> {
+        for (dir, matcher) in self.local.iter().rev() {
+            if !path.starts_with(dir) {
+                continue;
+            }
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(glob) => {
+                    return Some(format!(
+                        "excluded by '{}' in {}",
+                        glob.original(),
+                        dir.join(SORTIGNORE_FILE_NAME).to_string_lossy()
+                    ));
+                }
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+
+        match self.global.matched(path, is_dir) {
+            Match::Ignore(glob) => Some(format!("excluded by '{}' in excludePatterns", glob.original())),
+            _ => None,
+        }
+    }
+}
+
+/// Checks a global exclude pattern the way `ExcludeSet` will parse it.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    GitignoreBuilder::new("/")
+        .add_line(None, pattern)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
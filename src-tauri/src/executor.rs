@@ -1,10 +1,16 @@
-use crate::errors::AppResult;
-use crate::planner::{PlanEntry, PlanPreview, PlanSkip};
+use crate::errors::{AppError, AppResult};
+use crate::journal::WriteAhead;
+use crate::planner::{
+    CollisionAction, CollisionDecision, DuplicateMark, PlanEntry, PlanPreview, PlanSkip,
+};
+use crate::rules::DuplicateAction;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Emitter};
+use walkdir::WalkDir;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -13,16 +19,29 @@ pub struct MovedFile {
     pub destination_path: String,
     pub category: String,
     pub collision_renamed: bool,
+    #[serde(default)]
+    pub bundle: Option<String>,
+    /// Collision decision carried over from the plan entry.
+    #[serde(default)]
+    pub collision: Option<CollisionDecision>,
+    #[serde(default)]
+    pub duplicate: Option<DuplicateMark>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RunResult {
     pub session_id: String,
+    /// Profile whose sort root this run sorted.
+    #[serde(default)]
+    pub profile_id: Option<String>,
     pub started_at: String,
     pub finished_at: String,
     pub moved: u64,
     pub skipped: u64,
+    /// Skipped files a later pass may still move (see `PlanPreview::pending_count`).
+    #[serde(default)]
+    pub pending: u64,
     pub errors: u64,
     pub moved_files: Vec<MovedFile>,
     pub skips: Vec<PlanSkip>,
@@ -34,6 +53,7 @@ pub struct RunResult {
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RunProgressEvent {
+    profile_id: Option<String>,
     moved: u64,
     skipped: u64,
     errors: u64,
@@ -48,16 +68,27 @@ struct RunLogEvent {
     message: String,
 }
 
-pub fn execute_plan(app: &AppHandle, plan: &PlanPreview) -> AppResult<RunResult> {
-    execute_plan_impl(Some(app), plan)
+/// Executes `plan`, logging each move to `wal` before and after it happens.
+pub fn execute_plan(
+    app: &AppHandle,
+    profile_id: &str,
+    plan: &PlanPreview,
+    wal: &mut WriteAhead,
+) -> AppResult<RunResult> {
+    execute_plan_impl(Some(app), Some(profile_id), plan, Some(wal))
 }
 
 #[cfg(test)]
 pub fn execute_plan_silent(plan: &PlanPreview) -> AppResult<RunResult> {
-    execute_plan_impl(None, plan)
+    execute_plan_impl(None, None, plan, None)
 }
 
-fn execute_plan_impl(app: Option<&AppHandle>, plan: &PlanPreview) -> AppResult<RunResult> {
+fn execute_plan_impl(
+    app: Option<&AppHandle>,
+    profile_id: Option<&str>,
+    plan: &PlanPreview,
+    mut wal: Option<&mut WriteAhead>,
+) -> AppResult<RunResult> {
     let started_at = Utc::now().to_rfc3339();
     let mut moved = 0_u64;
     let mut errors = 0_u64;
@@ -67,18 +98,36 @@ fn execute_plan_impl(app: Option<&AppHandle>, plan: &PlanPreview) -> AppResult<R
     emit_log_opt(app, "info", format!("run started: {} planned moves", plan.move_count));
 
     for item in &plan.moves {
-        match move_entry(item) {
+        let moved_file = MovedFile {
+            source_path: item.source_path.clone(),
+            destination_path: item.destination_path.clone(),
+            category: item.category.clone(),
+            collision_renamed: item.collision_renamed,
+            bundle: item.bundle.clone(),
+            collision: item.collision.clone(),
+            duplicate: item.duplicate.clone(),
+        };
+        // A move whose intent cannot be logged is not attempted.
+        let outcome = match wal.as_deref_mut() {
+            Some(log) => log.intent(&moved_file).and_then(|()| move_entry(item)),
+            None => move_entry(item),
+        };
+
+        match outcome {
             Ok(()) => {
+                if let Some(Err(err)) = wal.as_deref_mut().map(|log| log.done(&item.source_path)) {
+                    emit_log_opt(
+                        app,
+                        "warn",
+                        format!("could not log completed move of '{}': {}", item.source_path, err),
+                    );
+                }
                 moved += 1;
-                moved_files.push(MovedFile {
-                    source_path: item.source_path.clone(),
-                    destination_path: item.destination_path.clone(),
-                    category: item.category.clone(),
-                    collision_renamed: item.collision_renamed,
-                });
+                moved_files.push(moved_file);
 
                 emit_progress_opt(
                     app,
+                    profile_id,
                     moved,
                     plan.skip_count,
                     errors,
@@ -99,6 +148,7 @@ fn execute_plan_impl(app: Option<&AppHandle>, plan: &PlanPreview) -> AppResult<R
                 );
                 emit_progress_opt(
                     app,
+                    profile_id,
                     moved,
                     plan.skip_count,
                     errors,
@@ -123,10 +173,12 @@ fn execute_plan_impl(app: Option<&AppHandle>, plan: &PlanPreview) -> AppResult<R
 
     Ok(RunResult {
         session_id: plan.session_id.clone(),
+        profile_id: profile_id.map(str::to_string),
         started_at,
         finished_at,
         moved,
         skipped: plan.skip_count,
+        pending: plan.pending_count,
         errors,
         moved_files,
         skips: plan.skips.clone(),
@@ -136,26 +188,169 @@ fn execute_plan_impl(app: Option<&AppHandle>, plan: &PlanPreview) -> AppResult<R
     })
 }
 
+/// Moves one entry, carrying out its collision decision. A destination that appeared
+/// after planning is never overwritten.
 fn move_entry(entry: &PlanEntry) -> AppResult<()> {
     let src = Path::new(&entry.source_path);
     let dest = Path::new(&entry.destination_path);
 
+    if is_trashed_duplicate(entry.duplicate.as_ref()) {
+        return trash_path(src);
+    }
+
+    match entry.collision.as_ref().map(|decision| decision.action) {
+        Some(CollisionAction::TrashedIncoming) => {
+            if !dest.exists() {
+                return Err(AppError::State(format!(
+                    "'{}' no longer exists; left the incoming file in place",
+                    entry.destination_path
+                )));
+            }
+            return trash_path(src);
+        }
+        Some(CollisionAction::ReplacedExisting) => {
+            if dest.exists() {
+                return replace_existing(src, dest);
+            }
+        }
+        Some(CollisionAction::Renamed) | None => {
+            if dest.exists() {
+                return Err(AppError::State(format!(
+                    "'{}' appeared after planning; run again to resolve the collision",
+                    entry.destination_path
+                )));
+            }
+        }
+    }
+
     if let Some(parent) = dest.parent() {
         fs::create_dir_all(parent)?;
     }
 
-    match fs::rename(src, dest) {
-        Ok(()) => Ok(()),
-        Err(_) => {
-            fs::copy(src, dest)?;
-            fs::remove_file(src)?;
-            Ok(())
+    move_path(src, dest)?;
+    Ok(())
+}
+
+/// Where a replacing move parks the incoming file until the existing one is in the Trash.
+pub fn replace_staging_path(dest: &Path) -> PathBuf {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    dest.with_file_name(format!(".{}.replacing", name))
+}
+
+/// Stages the incoming file next to `dest` before trashing the existing one, so a failed
+/// move never leaves the kept file in the Trash with nothing in its place.
+fn replace_existing(src: &Path, dest: &Path) -> AppResult<()> {
+    let staged = replace_staging_path(dest);
+    if staged.exists() {
+        return Err(AppError::State(format!(
+            "'{}' is left over from an interrupted replace; move or remove it first",
+            staged.to_string_lossy()
+        )));
+    }
+    move_path(src, &staged)?;
+
+    if let Err(err) = trash_path(dest) {
+        return match move_path(&staged, src) {
+            Ok(()) => Err(err),
+            Err(restore_err) => Err(AppError::State(format!(
+                "{}; the incoming file is at '{}' and could not be moved back: {}",
+                err,
+                staged.to_string_lossy(),
+                restore_err
+            ))),
+        };
+    }
+
+    fs::rename(&staged, dest).map_err(|err| {
+        AppError::State(format!(
+            "moved '{}' to the Trash but could not put the incoming file from '{}' in its \
+             place: {}",
+            dest.to_string_lossy(),
+            staged.to_string_lossy(),
+            err
+        ))
+    })
+}
+
+/// `true` for a duplicate the plan sends to the Trash instead of moving.
+pub fn is_trashed_duplicate(duplicate: Option<&DuplicateMark>) -> bool {
+    duplicate.is_some_and(|mark| mark.action == DuplicateAction::Trash)
+}
+
+fn trash_path(path: &Path) -> AppResult<()> {
+    move_to_trash(path).map_err(|err| {
+        AppError::State(format!("could not trash '{}': {}", path.to_string_lossy(), err))
+    })
+}
+
+/// Sends `path` to the system Trash. Every trashing code path goes through here.
+#[cfg(not(test))]
+pub(crate) fn move_to_trash(path: &Path) -> io::Result<()> {
+    trash::delete(path).map_err(io::Error::other)
+}
+
+/// Tests must not fill the real Trash: "trashed" paths are moved to a scratch folder.
+#[cfg(test)]
+pub(crate) fn move_to_trash(path: &Path) -> io::Result<()> {
+    let bin = std::env::temp_dir()
+        .join("secondbreakfast-test-trash")
+        .join(uuid::Uuid::new_v4().to_string());
+    fs::create_dir_all(&bin)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::other("path has no file name"))?;
+    move_path(path, &bin.join(name))
+}
+
+/// Renames `src` to `dest`, falling back to copy + delete across volumes.
+/// Directories (bundles) are copied recursively in the fallback.
+pub fn move_path(src: &Path, dest: &Path) -> io::Result<()> {
+    if fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+
+    if fs::symlink_metadata(src)?.is_dir() {
+        copy_dir_recursive(src, dest)?;
+        fs::remove_dir_all(src)
+    } else {
+        fs::copy(src, dest)?;
+        fs::remove_file(src)
+    }
+}
+
+fn copy_dir_recursive(src: &Path, dest: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let relative = entry
+            .path()
+            .strip_prefix(src)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        let target = dest.join(relative);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_symlink() {
+            copy_symlink(entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
         }
     }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dest)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::copy(src, dest).map(|_| ())
 }
 
 fn emit_progress_opt(
     app: Option<&AppHandle>,
+    profile_id: Option<&str>,
     moved: u64,
     skipped: u64,
     errors: u64,
@@ -169,6 +364,7 @@ fn emit_progress_opt(
     let _ = app.emit(
         "run_progress",
         RunProgressEvent {
+            profile_id: profile_id.map(str::to_string),
             moved,
             skipped,
             errors,
@@ -0,0 +1,32 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn file_digest(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// `true` when both paths are regular files with the same size and content hash.
+/// Unreadable files never count as identical.
+pub fn files_identical(a: &Path, b: &Path) -> bool {
+    let (Ok(meta_a), Ok(meta_b)) = (fs::metadata(a), fs::metadata(b)) else {
+        return false;
+    };
+    if !meta_a.is_file() || !meta_b.is_file() || meta_a.len() != meta_b.len() {
+        return false;
+    }
+
+    match (file_digest(a), file_digest(b)) {
+        (Ok(digest_a), Ok(digest_b)) => digest_a == digest_b,
+        _ => false,
+    }
+}
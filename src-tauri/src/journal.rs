@@ -1,21 +1,65 @@
-use crate::errors::AppResult;
-use crate::executor::MovedFile;
+use crate::errors::{AppError, AppResult};
+use crate::executor::{is_trashed_duplicate, move_path, replace_staging_path, MovedFile};
+use crate::planner::{CollisionAction, CollisionDecision, DuplicateMark, RedoCandidate};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{self, OpenOptions};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JournalRun {
-    #[serde(rename = "session_id", alias = "sessionId", alias = "run_id", alias = "runId")]
+    #[serde(
+        rename = "session_id",
+        alias = "sessionId",
+        alias = "run_id",
+        alias = "runId"
+    )]
     pub session_id: String,
-    #[serde(rename = "created_at", alias = "createdAt", default = "default_timestamp")]
+    #[serde(
+        rename = "created_at",
+        alias = "createdAt",
+        default = "default_timestamp"
+    )]
     pub created_at: String,
     pub moves: Vec<JournalMove>,
+    /// `RELOCATION_RUN_KIND` for sort-root migrations; absent for ordinary runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Sort root before a relocation, so undoing it can switch back.
+    #[serde(
+        rename = "previous_sort_root",
+        alias = "previousSortRoot",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub previous_sort_root: Option<String>,
+    /// For `undo` entries, the run that was undone; for `redo` runs, the `undo` entry that
+    /// was re-applied.
+    #[serde(
+        rename = "target_session_id",
+        alias = "targetSessionId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub target_session_id: Option<String>,
 }
 
+/// Journal kind of a run that moved the library to a new sort root.
+pub const RELOCATION_RUN_KIND: &str = "relocation";
+
+/// Journal kind of a `trash_duplicates` call; every move in it is `trashed`.
+pub const DUPLICATE_TRASH_RUN_KIND: &str = "duplicateTrash";
+
+/// Journal kind of an undo: each move goes from the undone run's `new_path` to where undo
+/// restored the file, with status `restored`. Undo entries are redone, never undone.
+pub const UNDO_RUN_KIND: &str = "undo";
+
+/// Journal kind of a run that re-applied an `undo` entry; it is undone like any other run.
+pub const REDO_RUN_KIND: &str = "redo";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JournalMove {
     #[serde(rename = "run_id", alias = "runId", default)]
@@ -40,6 +84,88 @@ pub struct JournalMove {
     pub timestamp: String,
     #[serde(default = "default_moved_status")]
     pub status: String,
+    /// Bundle rule name when the move relocated a whole directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<String>,
+    /// Collision decision taken for this file; `trashed` moves have nothing to undo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision: Option<CollisionDecision>,
+    /// Duplicate detection result; trashed duplicates are journaled as `trashed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate: Option<DuplicateMark>,
+    /// Where undo put the file back, once the move is `undone`.
+    #[serde(
+        rename = "restored_path",
+        alias = "restoredPath",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub restored_path: Option<String>,
+}
+
+/// Journal status of an incoming file the collision policy sent to the Trash.
+pub const TRASHED_STATUS: &str = "trashed";
+
+/// Journal status of a move that undo has reversed.
+pub const UNDONE_STATUS: &str = "undone";
+
+/// Journal status of an `undo` entry's move that redo can still re-apply.
+pub const RESTORED_STATUS: &str = "restored";
+
+/// Journal status of an `undo` entry's move that redo has re-applied.
+pub const REDONE_STATUS: &str = "redone";
+
+/// How much of a journaled run has been undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UndoState {
+    NotUndone,
+    PartiallyUndone,
+    Undone,
+    /// Every move was a trashed file; there is nothing undo could restore.
+    NothingToUndo,
+}
+
+/// One line of the journal index returned by `list_runs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalRunSummary {
+    pub session_id: String,
+    pub created_at: String,
+    pub kind: Option<String>,
+    /// See `JournalRun::target_session_id`.
+    pub target_session_id: Option<String>,
+    pub moves: u64,
+    /// Moves still in the `moved` state.
+    pub undoable: u64,
+    pub undone: u64,
+    pub trashed: u64,
+    /// Moves of an `undo` entry that `redo` can still re-apply.
+    pub redoable: u64,
+    pub undo_state: UndoState,
+}
+
+/// Where undo puts files back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UndoMode {
+    /// Under `<sort_root>/Restored/<session_id>/...`, mirroring the original absolute path.
+    #[default]
+    RestoredFolder,
+    /// Back at `original_path` while its folder still exists; a taken path gets a conflict
+    /// name next to it. Files whose folder is gone go to the `Restored` folder instead.
+    OriginalLocation,
+}
+
+/// Which moves of a run a selective undo restores. A move is selected when its original or
+/// new path is listed in `paths` or matches one of the glob `patterns`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoSelection {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +181,9 @@ pub struct UndoDetail {
 #[serde(rename_all = "camelCase")]
 pub struct UndoResult {
     pub session_id: Option<String>,
+    /// Session id of the `undo` entry journaled for this call; pass it to `redo`.
+    #[serde(default)]
+    pub undo_session_id: Option<String>,
     pub restored: u64,
     pub skipped: u64,
     pub conflicts: u64,
@@ -72,12 +201,110 @@ pub fn append_run(
     if moved_files.is_empty() {
         return Ok(());
     }
+    write_run(
+        path,
+        &new_run(session_id, moved_files, original_path_overrides),
+    )
+}
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+/// Journals a sort-root relocation. Undoing it moves files back to their original paths.
+pub fn append_relocation(
+    path: &Path,
+    session_id: &str,
+    moved_files: &[MovedFile],
+    previous_sort_root: &str,
+) -> AppResult<()> {
+    if moved_files.is_empty() {
+        return Ok(());
+    }
+    let mut run = new_run(session_id, moved_files, &HashMap::new());
+    run.kind = Some(RELOCATION_RUN_KIND.to_string());
+    run.previous_sort_root = Some(previous_sort_root.to_string());
+    write_run(path, &run)
+}
+
+/// Journals duplicates trashed from the library. Entries have no destination; the identical
+/// copy that was kept is `duplicate.original`.
+pub fn append_duplicate_trash(
+    path: &Path,
+    session_id: &str,
+    trashed: &[MovedFile],
+) -> AppResult<()> {
+    if trashed.is_empty() {
+        return Ok(());
+    }
+    let mut run = new_run(session_id, trashed, &HashMap::new());
+    run.kind = Some(DUPLICATE_TRASH_RUN_KIND.to_string());
+    write_run(path, &run)
+}
+
+/// The `undo` entry that `redo` re-applies: `session_id` names the entry itself or the run
+/// it undid (its newest undo entry); without one, the newest undo entry with moves left to
+/// redo is used.
+pub fn load_redo_source(path: &Path, session_id: Option<&str>) -> AppResult<JournalRun> {
+    let mut undo_entries = load_runs(path)?
+        .into_iter()
+        .rev()
+        .filter(|run| run.kind.as_deref() == Some(UNDO_RUN_KIND));
+    let found = match session_id {
+        Some(id) => undo_entries
+            .find(|run| run.session_id == id || run.target_session_id.as_deref() == Some(id)),
+        None => undo_entries.find(|run| redo_candidates(run).next().is_some()),
+    };
+    found.ok_or_else(|| match session_id {
+        Some(id) => AppError::Validation(format!("no undo of '{}' is journaled", id)),
+        None => AppError::Validation("there is no undo to redo".to_string()),
+    })
+}
+
+/// Moves of an `undo` entry that redo can still re-apply.
+pub fn redo_candidates(undo_entry: &JournalRun) -> impl Iterator<Item = RedoCandidate> + '_ {
+    undo_entry
+        .moves
+        .iter()
+        .filter(|movement| movement.status == RESTORED_STATUS)
+        .map(|movement| RedoCandidate {
+            restored_path: PathBuf::from(&movement.new_path),
+            new_path: PathBuf::from(&movement.original_path),
+            bundle: movement.bundle.clone(),
+        })
+}
+
+/// Journals a redo as an ordinary, undoable run and marks the moves it re-applied in
+/// `undo_entry` as `redone`.
+pub fn append_redo(
+    path: &Path,
+    session_id: &str,
+    moved_files: &[MovedFile],
+    undo_entry: &JournalRun,
+) -> AppResult<()> {
+    if moved_files.is_empty() {
+        return Ok(());
     }
+    let redone: HashSet<&str> = moved_files
+        .iter()
+        .map(|item| item.source_path.as_str())
+        .collect();
+    let mut undo_entry = undo_entry.clone();
+    for movement in &mut undo_entry.moves {
+        if movement.status == RESTORED_STATUS && redone.contains(movement.new_path.as_str()) {
+            movement.status = REDONE_STATUS.to_string();
+        }
+    }
+    replace_run(path, &undo_entry)?;
 
-    let run = JournalRun {
+    let mut run = new_run(session_id, moved_files, &HashMap::new());
+    run.kind = Some(REDO_RUN_KIND.to_string());
+    run.target_session_id = Some(undo_entry.session_id);
+    write_run(path, &run)
+}
+
+fn new_run(
+    session_id: &str,
+    moved_files: &[MovedFile],
+    original_path_overrides: &HashMap<String, String>,
+) -> JournalRun {
+    JournalRun {
         session_id: session_id.to_string(),
         created_at: Utc::now().to_rfc3339(),
         moves: moved_files
@@ -91,25 +318,106 @@ pub fn append_run(
                     .unwrap_or_else(|| item.source_path.clone()),
                 new_path: item.destination_path.clone(),
                 timestamp: Utc::now().to_rfc3339(),
-                status: default_moved_status(),
+                status: if is_trashed_incoming(item) {
+                    TRASHED_STATUS.to_string()
+                } else {
+                    default_moved_status()
+                },
+                bundle: item.bundle.clone(),
+                collision: item.collision.clone(),
+                duplicate: item.duplicate.clone(),
+                restored_path: None,
             })
             .collect(),
-    };
+        kind: None,
+        previous_sort_root: None,
+        target_session_id: None,
+    }
+}
+
+fn is_trashed_incoming(item: &MovedFile) -> bool {
+    is_trashed_duplicate(item.duplicate.as_ref())
+        || item
+            .collision
+            .as_ref()
+            .is_some_and(|decision| decision.action == CollisionAction::TrashedIncoming)
+}
+
+fn write_run(path: &Path, run: &JournalRun) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
 
     let mut file = OpenOptions::new().create(true).append(true).open(path)?;
-    let line = serde_json::to_string(&run)?;
+    let line = serde_json::to_string(run)?;
     writeln!(file, "{}", line)?;
     Ok(())
 }
 
+/// The newest run that moved files. `undo` entries are skipped, and so are runs that only
+/// trashed files (such as `duplicateTrash` runs), since undo has nothing to bring back.
 pub fn load_last_run(path: &Path) -> AppResult<Option<JournalRun>> {
+    Ok(load_runs(path)?.into_iter().rev().find(|run| {
+        run.kind.as_deref() != Some(UNDO_RUN_KIND)
+            && run
+                .moves
+                .iter()
+                .any(|movement| movement.status != TRASHED_STATUS)
+    }))
+}
+
+pub fn load_run(path: &Path, session_id: &str) -> AppResult<Option<JournalRun>> {
+    Ok(load_runs(path)?
+        .into_iter()
+        .rev()
+        .find(|run| run.session_id == session_id))
+}
+
+/// The journal index, newest run first.
+pub fn list_runs(path: &Path) -> AppResult<Vec<JournalRunSummary>> {
+    Ok(load_runs(path)?
+        .into_iter()
+        .rev()
+        .map(|run| {
+            let count = |status: &str| {
+                run.moves
+                    .iter()
+                    .filter(|movement| movement.status == status)
+                    .count() as u64
+            };
+            let undoable = count("moved");
+            let undone = count(UNDONE_STATUS);
+            let undo_state = match (undoable, undone) {
+                (0, 0) => UndoState::NothingToUndo,
+                (_, 0) => UndoState::NotUndone,
+                (0, _) => UndoState::Undone,
+                _ => UndoState::PartiallyUndone,
+            };
+            JournalRunSummary {
+                trashed: count(TRASHED_STATUS),
+                redoable: count(RESTORED_STATUS),
+                session_id: run.session_id,
+                created_at: run.created_at,
+                kind: run.kind,
+                target_session_id: run.target_session_id,
+                moves: run.moves.len() as u64,
+                undoable,
+                undone,
+                undo_state,
+            }
+        })
+        .collect())
+}
+
+/// Every run in the journal, oldest first. Lines that do not parse are ignored.
+pub fn load_runs(path: &Path) -> AppResult<Vec<JournalRun>> {
     if !path.exists() {
-        return Ok(None);
+        return Ok(Vec::new());
     }
 
     let file = fs::File::open(path)?;
     let reader = BufReader::new(file);
-    let mut last = None;
+    let mut runs = Vec::new();
 
     for line in reader.lines() {
         let line = line?;
@@ -127,11 +435,376 @@ pub fn load_last_run(path: &Path) -> AppResult<Option<JournalRun>> {
                     movement.status = default_moved_status();
                 }
             }
-            last = Some(run);
+            runs.push(run);
+        }
+    }
+
+    Ok(runs)
+}
+
+/// Write-ahead log of one run, kept next to the journal as `journal.<session id>.wal`. Every
+/// move is recorded before it happens and again once it is done, and the log is removed
+/// after the run's journal entry is written. A log left behind means the app stopped or the
+/// run failed mid-way; `recover_unfinished_runs` turns it back into journal entries. Each
+/// run has its own file, so finishing one never discards another's records.
+pub struct WriteAhead {
+    path: PathBuf,
+    session_id: String,
+    file: File,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum WalRecord {
+    Begin {
+        session_id: String,
+        #[serde(default)]
+        kind: Option<String>,
+        #[serde(default)]
+        previous_sort_root: Option<String>,
+        /// For a redo, the `undo` entry it re-applies.
+        #[serde(default)]
+        target_session_id: Option<String>,
+    },
+    Intent {
+        session_id: String,
+        entry: MovedFile,
+    },
+    Done {
+        session_id: String,
+        source_path: String,
+    },
+}
+
+pub fn write_ahead_path(journal_path: &Path, session_id: &str) -> PathBuf {
+    journal_path.with_extension(format!("{}.wal", session_id))
+}
+
+/// Every write-ahead log next to the journal, including a shared `journal.wal` left by
+/// earlier versions.
+fn write_ahead_logs(journal_path: &Path) -> AppResult<Vec<PathBuf>> {
+    let (Some(dir), Some(stem)) = (journal_path.parent(), journal_path.file_stem()) else {
+        return Ok(Vec::new());
+    };
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{}.", stem.to_string_lossy());
+
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if name.starts_with(&prefix) && name.ends_with(".wal") && path.is_file() {
+            logs.push(path);
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+impl WriteAhead {
+    pub fn begin(
+        journal_path: &Path,
+        session_id: &str,
+        kind: Option<&str>,
+        previous_sort_root: Option<&str>,
+    ) -> AppResult<Self> {
+        Self::open(
+            journal_path,
+            session_id,
+            WalRecord::Begin {
+                session_id: session_id.to_string(),
+                kind: kind.map(str::to_string),
+                previous_sort_root: previous_sort_root.map(str::to_string),
+                target_session_id: None,
+            },
+        )
+    }
+
+    /// Starts the log of a redo of `undo_session_id`, so recovery can still mark that
+    /// entry's moves `redone`.
+    pub fn begin_redo(
+        journal_path: &Path,
+        session_id: &str,
+        undo_session_id: &str,
+    ) -> AppResult<Self> {
+        Self::open(
+            journal_path,
+            session_id,
+            WalRecord::Begin {
+                session_id: session_id.to_string(),
+                kind: Some(REDO_RUN_KIND.to_string()),
+                previous_sort_root: None,
+                target_session_id: Some(undo_session_id.to_string()),
+            },
+        )
+    }
+
+    fn open(journal_path: &Path, session_id: &str, begin: WalRecord) -> AppResult<Self> {
+        let path = write_ahead_path(journal_path, session_id);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let mut log = Self {
+            path,
+            session_id: session_id.to_string(),
+            file,
+        };
+        log.write(&begin)?;
+        Ok(log)
+    }
+
+    /// Records a move that is about to happen.
+    pub fn intent(&mut self, entry: &MovedFile) -> AppResult<()> {
+        self.write(&WalRecord::Intent {
+            session_id: self.session_id.clone(),
+            entry: entry.clone(),
+        })
+    }
+
+    /// Records that the move of `source_path` completed.
+    pub fn done(&mut self, source_path: &str) -> AppResult<()> {
+        self.write(&WalRecord::Done {
+            session_id: self.session_id.clone(),
+            source_path: source_path.to_string(),
+        })
+    }
+
+    /// Removes the log; call once the run's journal entry is written.
+    pub fn finish(self) -> AppResult<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&mut self, record: &WalRecord) -> AppResult<()> {
+        let line = serde_json::to_string(record)?;
+        writeln!(self.file, "{}", line)?;
+        // An intent must be on disk before its move starts.
+        if matches!(record, WalRecord::Intent { .. }) {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// A logged move whose outcome could not be told from the file system.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedMove {
+    pub source_path: String,
+    pub destination_path: String,
+    pub reason: String,
+}
+
+/// What startup recovery made of one interrupted run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredRun {
+    pub session_id: String,
+    /// Moves that had happened and are now journaled, so undo can reverse them.
+    pub journaled: u64,
+    /// Moves that never started; the files are still at their source.
+    pub not_started: u64,
+    pub orphaned: Vec<OrphanedMove>,
+    /// Why the log could not be replayed; it is left in place for the next start.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl RecoveredRun {
+    /// A report for a recovery that could not run at all.
+    pub fn failed(source: &Path, err: &AppError) -> Self {
+        Self {
+            session_id: String::new(),
+            journaled: 0,
+            not_started: 0,
+            orphaned: Vec::new(),
+            error: Some(format!("could not recover '{}': {}", source.display(), err)),
         }
     }
+}
+
+/// Replays the write-ahead logs left by interrupted runs. Moves logged as done, or whose
+/// file is at the destination and gone from the source, are appended to the journal as the
+/// run's entry; moves that never started are dropped, and anything else is reported as
+/// orphaned. Each log is removed once replayed; one that cannot be read is reported and kept.
+pub fn recover_unfinished_runs(journal_path: &Path) -> AppResult<Vec<RecoveredRun>> {
+    let logs = write_ahead_logs(journal_path)?;
+    if logs.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    Ok(last)
+    let journaled_sessions: HashSet<String> = load_runs(journal_path)?
+        .into_iter()
+        .map(|run| run.session_id)
+        .collect();
+
+    let mut recovered = Vec::new();
+    for log in logs {
+        match replay_write_ahead_log(journal_path, &log, &journaled_sessions) {
+            Ok(runs) => {
+                recovered.extend(runs);
+                fs::remove_file(&log)?;
+            }
+            Err(err) => recovered.push(RecoveredRun::failed(&log, &err)),
+        }
+    }
+    Ok(recovered)
+}
+
+fn replay_write_ahead_log(
+    journal_path: &Path,
+    wal_path: &Path,
+    journaled_sessions: &HashSet<String>,
+) -> AppResult<Vec<RecoveredRun>> {
+    struct Pending {
+        session_id: String,
+        kind: Option<String>,
+        previous_sort_root: Option<String>,
+        target_session_id: Option<String>,
+        intents: Vec<MovedFile>,
+        done: HashSet<String>,
+    }
+
+    let mut sessions: Vec<Pending> = Vec::new();
+    let content = fs::read_to_string(wal_path)?;
+    for line in content.lines() {
+        // A torn final line is expected after a crash.
+        let Ok(record) = serde_json::from_str::<WalRecord>(line.trim()) else {
+            continue;
+        };
+        let session_id = match &record {
+            WalRecord::Begin { session_id, .. }
+            | WalRecord::Intent { session_id, .. }
+            | WalRecord::Done { session_id, .. } => session_id.clone(),
+        };
+        let index = match sessions
+            .iter()
+            .position(|pending| pending.session_id == session_id)
+        {
+            Some(index) => index,
+            None => {
+                sessions.push(Pending {
+                    session_id,
+                    kind: None,
+                    previous_sort_root: None,
+                    target_session_id: None,
+                    intents: Vec::new(),
+                    done: HashSet::new(),
+                });
+                sessions.len() - 1
+            }
+        };
+        let pending = &mut sessions[index];
+        match record {
+            WalRecord::Begin {
+                kind,
+                previous_sort_root,
+                target_session_id,
+                ..
+            } => {
+                pending.kind = kind;
+                pending.previous_sort_root = previous_sort_root;
+                pending.target_session_id = target_session_id;
+            }
+            WalRecord::Intent { entry, .. } => pending.intents.push(entry),
+            WalRecord::Done { source_path, .. } => {
+                pending.done.insert(source_path);
+            }
+        }
+    }
+
+    let mut recovered = Vec::new();
+    for pending in sessions {
+        // The run finished; only removing the log was lost.
+        if journaled_sessions.contains(&pending.session_id) {
+            continue;
+        }
+
+        let mut report = RecoveredRun {
+            session_id: pending.session_id.clone(),
+            journaled: 0,
+            not_started: 0,
+            orphaned: Vec::new(),
+            error: None,
+        };
+        let mut completed = Vec::new();
+
+        for entry in pending.intents {
+            if pending.done.contains(&entry.source_path) {
+                completed.push(entry);
+                continue;
+            }
+
+            let trashes = is_trashed_incoming(&entry);
+            // The file a replacing move trashes sits at the destination until the move starts.
+            let replaces = entry
+                .collision
+                .as_ref()
+                .is_some_and(|decision| decision.action == CollisionAction::ReplacedExisting);
+            let source_exists = Path::new(&entry.source_path).exists();
+            let destination_exists = Path::new(&entry.destination_path).exists();
+            let staged =
+                replaces && replace_staging_path(Path::new(&entry.destination_path)).exists();
+            // A trashed incoming file has no destination of its own: it is done once the
+            // source is gone.
+            let orphan_reason = match (source_exists, destination_exists) {
+                _ if staged => Some(
+                    "the replace stopped after staging the incoming file beside the destination",
+                ),
+                (true, true) if !trashes && !replaces => Some(
+                    "both the source and the destination exist; the move may have stopped mid-copy",
+                ),
+                (true, _) => {
+                    report.not_started += 1;
+                    continue;
+                }
+                (false, false) if !trashes => {
+                    Some("the file is at neither the source nor the destination")
+                }
+                (false, _) => None,
+            };
+
+            match orphan_reason {
+                None => completed.push(entry),
+                Some(reason) => report.orphaned.push(OrphanedMove {
+                    source_path: entry.source_path,
+                    destination_path: entry.destination_path,
+                    reason: reason.to_string(),
+                }),
+            }
+        }
+
+        // A redo also marks what it re-applied in its undo entry, as `append_redo` does.
+        let undo_entry = match &pending.target_session_id {
+            Some(id) => load_run(journal_path, id)?,
+            None => None,
+        };
+        if let Some(undo_entry) = undo_entry {
+            append_redo(journal_path, &pending.session_id, &completed, &undo_entry)?;
+        } else if !completed.is_empty() {
+            let mut run = new_run(&pending.session_id, &completed, &HashMap::new());
+            run.kind = pending.kind;
+            run.previous_sort_root = pending.previous_sort_root;
+            run.target_session_id = pending.target_session_id;
+            write_run(journal_path, &run)?;
+        }
+        report.journaled = completed.len() as u64;
+
+        if report.journaled > 0 || report.not_started > 0 || !report.orphaned.is_empty() {
+            recovered.push(report);
+        }
+    }
+
+    Ok(recovered)
 }
 
 /// Convert an absolute path into a safe *relative* path that preserves structure.
@@ -170,12 +843,12 @@ fn absolute_to_safe_relative(original: &Path) -> Option<PathBuf> {
     Some(rel)
 }
 
-/// Undo restores into `<sort_root>/Restored/<session_id>/...`
-/// preserving the original absolute path structure as a relative tree.
-pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
+/// Undoes the newest run in the journal (see `undo_run`).
+pub fn undo_last_run(path: &Path, sort_root: &Path, mode: UndoMode) -> AppResult<UndoResult> {
     let Some(last) = load_last_run(path)? else {
         return Ok(UndoResult {
             session_id: None,
+            undo_session_id: None,
             restored: 0,
             skipped: 0,
             conflicts: 0,
@@ -184,13 +857,107 @@ pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
             details: Vec::new(),
         });
     };
+    undo_run(path, &last.session_id, sort_root, mode)
+}
+
+/// Undo restores into `<sort_root>/Restored/<session_id>/...`
+/// preserving the original absolute path structure as a relative tree.
+/// Relocations are reversed in place instead, falling back to `Restored` when the
+/// original path has been taken since. `UndoMode::OriginalLocation` restores ordinary runs
+/// in place as well. Restored moves are marked `undone` in the journal,
+/// so undoing the same run again skips them.
+pub fn undo_run(
+    path: &Path,
+    session_id: &str,
+    sort_root: &Path,
+    mode: UndoMode,
+) -> AppResult<UndoResult> {
+    undo_moves(path, session_id, sort_root, mode, |_| true)
+}
+
+/// Undoes only the moves of `session_id` picked by `selection`; the rest of the run stays
+/// as it is and the run is listed as partially undone. Listed paths that are not part of
+/// the run are reported as skipped.
+pub fn undo_selected(
+    path: &Path,
+    session_id: &str,
+    sort_root: &Path,
+    mode: UndoMode,
+    selection: &UndoSelection,
+) -> AppResult<UndoResult> {
+    if selection.paths.is_empty() && selection.patterns.is_empty() {
+        return Err(AppError::Validation(
+            "select at least one path or pattern to undo".to_string(),
+        ));
+    }
+    let mut patterns = Vec::new();
+    for pattern in &selection.patterns {
+        let compiled = glob::Pattern::new(pattern).map_err(|err| {
+            AppError::Validation(format!("invalid undo pattern '{}': {}", pattern, err))
+        })?;
+        patterns.push(compiled);
+    }
+    let selected = |movement: &JournalMove| {
+        [&movement.original_path, &movement.new_path]
+            .into_iter()
+            .any(|candidate| {
+                selection.paths.contains(candidate)
+                    || patterns.iter().any(|pattern| pattern.matches(candidate))
+            })
+    };
+
+    let journaled = load_run(path, session_id)?
+        .map(|run| run.moves)
+        .unwrap_or_default();
+    let mut result = undo_moves(path, session_id, sort_root, mode, selected)?;
+    for requested in &selection.paths {
+        let known = journaled.iter().any(|movement| {
+            &movement.original_path == requested || &movement.new_path == requested
+        });
+        if !known {
+            result.skipped += 1;
+            result.details.push(UndoDetail {
+                source_path: requested.clone(),
+                destination_path: String::new(),
+                status: "skipped".to_string(),
+                message: "not part of this run".to_string(),
+            });
+        }
+    }
+    Ok(result)
+}
+
+fn undo_moves(
+    path: &Path,
+    session_id: &str,
+    sort_root: &Path,
+    mode: UndoMode,
+    selected: impl Fn(&JournalMove) -> bool,
+) -> AppResult<UndoResult> {
+    let Some(mut last) = load_run(path, session_id)? else {
+        return Err(AppError::Validation(format!(
+            "no journaled run with session id '{}'",
+            session_id
+        )));
+    };
+    if last.kind.as_deref() == Some(UNDO_RUN_KIND) {
+        return Err(AppError::Validation(format!(
+            "'{}' is an undo; use redo to re-apply the run it undid",
+            session_id
+        )));
+    }
 
     // Deterministic restore base: <sort_root>/Restored/<session_id>
     let restored_base = sort_root.join("Restored").join(&last.session_id);
-    fs::create_dir_all(&restored_base)?;
+    let relocation = last.kind.as_deref() == Some(RELOCATION_RUN_KIND);
+    let to_original = mode == UndoMode::OriginalLocation;
+    if !relocation && !to_original {
+        fs::create_dir_all(&restored_base)?;
+    }
 
     let mut result = UndoResult {
         session_id: Some(last.session_id.clone()),
+        undo_session_id: None,
         restored: 0,
         skipped: 0,
         conflicts: 0,
@@ -199,7 +966,35 @@ pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
         details: Vec::new(),
     };
 
-    for movement in last.moves.iter().rev() {
+    let mut restored_moves = Vec::new();
+    for movement in last.moves.iter_mut().rev() {
+        if !selected(movement) {
+            continue;
+        }
+
+        if movement.status == UNDONE_STATUS {
+            result.skipped += 1;
+            result.details.push(UndoDetail {
+                source_path: movement.original_path.clone(),
+                destination_path: movement.new_path.clone(),
+                status: "skipped".to_string(),
+                message: "already undone".to_string(),
+            });
+            continue;
+        }
+
+        if movement.status == TRASHED_STATUS {
+            result.skipped += 1;
+            result.details.push(UndoDetail {
+                source_path: movement.original_path.clone(),
+                destination_path: movement.new_path.clone(),
+                status: "skipped".to_string(),
+                message: "the incoming file was moved to the Trash; restore it from there"
+                    .to_string(),
+            });
+            continue;
+        }
+
         if movement.status != "moved" {
             result.skipped += 1;
             result.details.push(UndoDetail {
@@ -247,19 +1042,27 @@ pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
             continue;
         }
 
-        // Convert original absolute path into a safe relative tree under Restored/<session_id>.
-        let Some(rel) = absolute_to_safe_relative(&original) else {
-            result.skipped += 1;
-            result.details.push(UndoDetail {
-                source_path: movement.original_path.clone(),
-                destination_path: movement.new_path.clone(),
-                status: "skipped".to_string(),
-                message: "could not derive safe relative restore path".to_string(),
-            });
-            continue;
+        let in_place = if relocation {
+            !original.exists() || to_original
+        } else {
+            to_original && original.parent().is_some_and(Path::is_dir)
+        };
+        let mut target = if in_place {
+            original.clone()
+        } else {
+            // Convert original absolute path into a safe relative tree under Restored/<session_id>.
+            let Some(rel) = absolute_to_safe_relative(&original) else {
+                result.skipped += 1;
+                result.details.push(UndoDetail {
+                    source_path: movement.original_path.clone(),
+                    destination_path: movement.new_path.clone(),
+                    status: "skipped".to_string(),
+                    message: "could not derive safe relative restore path".to_string(),
+                });
+                continue;
+            };
+            restored_base.join(rel)
         };
-
-        let mut target = restored_base.join(rel);
 
         let mut conflict_target = None;
         if target.exists() {
@@ -269,25 +1072,52 @@ pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
             result.conflicts += 1;
         }
 
-        if let Some(parent) = target.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        match move_file(&current, &target) {
+        let restored = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| move_path(&current, &target));
+        match restored {
             Ok(()) => {
                 result.restored += 1;
+                movement.status = UNDONE_STATUS.to_string();
+                movement.restored_path = Some(target.to_string_lossy().to_string());
+                restored_moves.push(JournalMove {
+                    run_id: String::new(),
+                    original_path: movement.new_path.clone(),
+                    new_path: target.to_string_lossy().to_string(),
+                    timestamp: Utc::now().to_rfc3339(),
+                    status: RESTORED_STATUS.to_string(),
+                    bundle: movement.bundle.clone(),
+                    collision: None,
+                    duplicate: None,
+                    restored_path: None,
+                });
 
                 let (status, message) = if let Some(conflict) = conflict_target {
                     (
                         "conflict".to_string(),
                         format!("restored to conflict path {}", conflict.to_string_lossy()),
                     )
+                } else if target == original {
+                    (
+                        "restored".to_string(),
+                        "restored to original location".to_string(),
+                    )
                 } else {
                     (
                         "restored".to_string(),
                         format!("restored under {}", restored_base.to_string_lossy()),
                     )
                 };
+                let replaced = movement
+                    .collision
+                    .as_ref()
+                    .is_some_and(|decision| decision.action == CollisionAction::ReplacedExisting);
+                let message = if replaced {
+                    format!("{}; the file it replaced is in the Trash", message)
+                } else {
+                    message
+                };
 
                 result.details.push(UndoDetail {
                     source_path: movement.original_path.clone(),
@@ -308,15 +1138,53 @@ pub fn undo_last_run(path: &Path, sort_root: &Path) -> AppResult<UndoResult> {
         }
     }
 
+    if result.restored > 0 {
+        replace_run(path, &last)?;
+
+        let undo_session_id = Uuid::new_v4().to_string();
+        for movement in &mut restored_moves {
+            movement.run_id = undo_session_id.clone();
+        }
+        write_run(
+            path,
+            &JournalRun {
+                session_id: undo_session_id.clone(),
+                created_at: Utc::now().to_rfc3339(),
+                moves: restored_moves,
+                kind: Some(UNDO_RUN_KIND.to_string()),
+                previous_sort_root: None,
+                target_session_id: Some(last.session_id.clone()),
+            },
+        )?;
+        result.undo_session_id = Some(undo_session_id);
+    }
     Ok(result)
 }
 
-fn move_file(src: &Path, dest: &Path) -> Result<(), std::io::Error> {
-    std::fs::rename(src, dest).or_else(|_| {
-        std::fs::copy(src, dest)?;
-        std::fs::remove_file(src)?;
-        Ok(())
-    })
+/// Rewrites the journal line of `run.session_id`, leaving every other line untouched.
+/// The new file is written next to the journal and renamed over it.
+fn replace_run(path: &Path, run: &JournalRun) -> AppResult<()> {
+    let content = fs::read_to_string(path)?;
+    let replacement = serde_json::to_string(run)?;
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let is_target = serde_json::from_str::<JournalRun>(line.trim())
+            .is_ok_and(|existing| existing.session_id == run.session_id);
+        lines.push(if is_target {
+            replacement.clone()
+        } else {
+            line.to_string()
+        });
+    }
+
+    let temp_path = path.with_extension("jsonl.tmp");
+    let mut file = File::create(&temp_path)?;
+    for line in &lines {
+        writeln!(file, "{}", line)?;
+    }
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
 }
 
 fn resolve_restored_conflict_path(original: &Path) -> PathBuf {
@@ -397,7 +1265,8 @@ mod tests {
         });
         fs::write(&journal_path, format!("{}\n", legacy)).expect("write legacy journal");
 
-        let result = undo_last_run(&journal_path, &root).expect("undo run");
+        let result =
+            undo_last_run(&journal_path, &root, UndoMode::RestoredFolder).expect("undo run");
 
         assert_eq!(result.restored, 1);
         assert!(!destination.exists());
@@ -416,7 +1285,11 @@ mod tests {
 
         let journal_path = root.join("journal.jsonl");
         let moved = vec![MovedFile {
-            source_path: root.join("Drop").join("a.txt").to_string_lossy().to_string(),
+            source_path: root
+                .join("Drop")
+                .join("a.txt")
+                .to_string_lossy()
+                .to_string(),
             destination_path: root
                 .join("Documents")
                 .join("a.txt")
@@ -424,6 +1297,9 @@ mod tests {
                 .to_string(),
             category: "Documents".to_string(),
             collision_renamed: false,
+            bundle: None,
+            collision: None,
+            duplicate: None,
         }];
 
         let overrides = HashMap::new();
@@ -475,7 +1351,8 @@ mod tests {
         });
         fs::write(&journal_path, format!("{}\n", entry)).expect("write journal");
 
-        let result = undo_last_run(&journal_path, &root).expect("undo run");
+        let result =
+            undo_last_run(&journal_path, &root, UndoMode::RestoredFolder).expect("undo run");
 
         assert_eq!(result.errors, 0);
         assert_eq!(result.restored, 1);
@@ -485,4 +1362,97 @@ mod tests {
 
         let _ = fs::remove_dir_all(&root);
     }
+
+    #[test]
+    fn interrupted_runs_are_recovered_from_the_write_ahead_log() {
+        let root = temp_dir();
+        let journal_path = root.join("journal.jsonl");
+        let entry = |name: &str| MovedFile {
+            source_path: root.join("Drop").join(name).to_string_lossy().to_string(),
+            destination_path: root
+                .join("Documents")
+                .join(name)
+                .to_string_lossy()
+                .to_string(),
+            category: "Documents".to_string(),
+            collision_renamed: false,
+            bundle: None,
+            collision: None,
+            duplicate: None,
+        };
+        for name in ["done.txt", "unlogged.txt", "pending.txt", "copied.txt"] {
+            fs::create_dir_all(root.join("Drop")).expect("create drop");
+            fs::write(root.join("Drop").join(name), name).expect("write source");
+        }
+        fs::create_dir_all(root.join("Documents")).expect("create documents");
+
+        let mut wal = WriteAhead::begin(&journal_path, "run-1", None, None).expect("begin");
+        for name in ["done.txt", "unlogged.txt"] {
+            let moved = entry(name);
+            wal.intent(&moved).expect("log intent");
+            move_path(
+                Path::new(&moved.source_path),
+                Path::new(&moved.destination_path),
+            )
+            .expect("move");
+            if name == "done.txt" {
+                wal.done(&moved.source_path).expect("log done");
+            }
+        }
+        wal.intent(&entry("pending.txt")).expect("log intent");
+        let copied = entry("copied.txt");
+        wal.intent(&copied).expect("log intent");
+        fs::copy(&copied.source_path, &copied.destination_path).expect("partial copy");
+        // The file a replacing move would trash is still there: the move never started.
+        let replacing = |name: &str| MovedFile {
+            collision: Some(CollisionDecision {
+                policy: crate::rules::CollisionPolicy::OverwriteToTrash,
+                action: CollisionAction::ReplacedExisting,
+                reason: "existing file moved to Trash".to_string(),
+            }),
+            ..entry(name)
+        };
+        let unstarted = replacing("replacing.txt");
+        fs::write(&unstarted.source_path, "incoming").expect("write source");
+        fs::write(&unstarted.destination_path, "existing").expect("write existing");
+        wal.intent(&unstarted).expect("log intent");
+        // Staged beside the destination but never put in place: neither done nor unstarted.
+        let staged = replacing("staged.txt");
+        let destination = Path::new(&staged.destination_path);
+        fs::write(replace_staging_path(destination), "incoming").expect("write staged");
+        fs::write(destination, "existing").expect("write existing");
+        wal.intent(&staged).expect("log intent");
+        drop(wal);
+
+        // A later run that finishes cleanly leaves the interrupted run's log alone.
+        let other = WriteAhead::begin(&journal_path, "run-2", None, None).expect("begin");
+        other.finish().expect("finish");
+        assert!(write_ahead_path(&journal_path, "run-1").exists());
+
+        let recovered = recover_unfinished_runs(&journal_path).expect("recover");
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].journaled, 2);
+        assert_eq!(recovered[0].not_started, 2);
+        let orphaned: HashSet<&str> = recovered[0]
+            .orphaned
+            .iter()
+            .map(|orphan| orphan.source_path.as_str())
+            .collect();
+        assert_eq!(
+            orphaned,
+            HashSet::from([copied.source_path.as_str(), staged.source_path.as_str()])
+        );
+        assert!(!write_ahead_path(&journal_path, "run-1").exists());
+
+        let run = load_last_run(&journal_path)
+            .expect("load")
+            .expect("recovered run");
+        assert_eq!(run.session_id, "run-1");
+        assert_eq!(run.moves.len(), 2);
+        assert!(recover_unfinished_runs(&journal_path)
+            .expect("second pass")
+            .is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
 }
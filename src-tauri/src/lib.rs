@@ -1,18 +1,27 @@
 mod cleanup;
+mod duplicates;
 mod errors;
+mod exclude;
 mod executor;
+mod hash;
 mod journal;
+mod migrations;
+mod openfiles;
 mod planner;
 mod rules;
+mod sniff;
+mod template;
 mod watcher;
 
 use crate::errors::{AppError, AppResult};
 use cleanup::CleanupResult;
+use duplicates::{DuplicateReport, DuplicateTrashResult};
 use executor::RunResult;
-use planner::PlanPreview;
+use planner::{PlanPreview, RunMode, SizeSamples};
 use rules::{Rules, ValidationResult};
 
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -23,7 +32,14 @@ use watcher::{DebouncedAction, EventObserver, WatcherController, WatcherStatus};
 
 #[derive(Clone)]
 struct AppState {
-    inner: Arc<AppStateInner>,
+    profiles: Arc<Mutex<BTreeMap<String, Profile>>>,
+}
+
+/// One sort root with its own rules, watcher, journal and undo history.
+#[derive(Clone)]
+struct Profile {
+    id: String,
+    inner: Arc<ProfileInner>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,7 +48,7 @@ struct OriginHint {
     original_path: PathBuf,
 }
 
-struct AppStateInner {
+struct ProfileInner {
     rules: Mutex<Rules>,
     rules_path: PathBuf,
     journal_path: PathBuf,
@@ -40,12 +56,42 @@ struct AppStateInner {
     pipeline_running: AtomicBool,
     undo_in_progress: AtomicBool,
     origin_hints: Mutex<Vec<OriginHint>>,
+    size_samples: Mutex<SizeSamples>,
+    /// Runs interrupted before their journal entry was written, replayed at startup.
+    recovered_runs: Mutex<Vec<journal::RecoveredRun>>,
 }
 
 impl AppState {
-    fn new(rules: Rules, rules_path: PathBuf, journal_path: PathBuf) -> Self {
+    fn new(profiles: Vec<Profile>) -> Self {
+        let profiles = profiles
+            .into_iter()
+            .map(|profile| (profile.id.clone(), profile))
+            .collect();
+        Self {
+            profiles: Arc::new(Mutex::new(profiles)),
+        }
+    }
+
+    /// Looks up a profile; `None` selects the default profile so single-root callers keep working.
+    fn profile(&self, id: Option<&str>) -> AppResult<Profile> {
+        let id = id.unwrap_or(rules::DEFAULT_PROFILE_ID);
+        self.profiles
+            .lock()?
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::State(format!("unknown profile '{}'", id)))
+    }
+
+    fn all_profiles(&self) -> AppResult<Vec<Profile>> {
+        Ok(self.profiles.lock()?.values().cloned().collect())
+    }
+}
+
+impl Profile {
+    fn new(id: String, rules: Rules, rules_path: PathBuf, journal_path: PathBuf) -> Self {
         Self {
-            inner: Arc::new(AppStateInner {
+            id,
+            inner: Arc::new(ProfileInner {
                 rules: Mutex::new(rules),
                 rules_path,
                 journal_path,
@@ -53,6 +99,8 @@ impl AppState {
                 pipeline_running: AtomicBool::new(false),
                 undo_in_progress: AtomicBool::new(false),
                 origin_hints: Mutex::new(Vec::new()),
+                size_samples: Mutex::new(SizeSamples::new()),
+                recovered_runs: Mutex::new(Vec::new()),
             }),
         }
     }
@@ -66,6 +114,11 @@ impl AppState {
         Ok(())
     }
 
+    fn set_recovered_runs(&self, runs: Vec<journal::RecoveredRun>) -> AppResult<()> {
+        *self.inner.recovered_runs.lock()? = runs;
+        Ok(())
+    }
+
     fn watcher_running(&self) -> AppResult<bool> {
         Ok(self.inner.watcher.lock()?.running)
     }
@@ -107,14 +160,76 @@ impl Drop for BoolGuard<'_> {
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileSummary {
+    id: String,
+    sort_root: String,
+    watcher_running: bool,
+}
+
+#[tauri::command]
+fn list_profiles(state: State<AppState>) -> Result<Vec<ProfileSummary>, String> {
+    map_err(list_profiles_internal(state.inner()))
+}
+
+#[tauri::command]
+fn create_profile(
+    state: State<AppState>,
+    id: String,
+    sort_root: String,
+) -> Result<ProfileSummary, String> {
+    map_err(create_profile_internal(state.inner(), id, sort_root))
+}
+
+#[tauri::command]
+fn delete_profile(app: AppHandle, state: State<AppState>, id: String) -> Result<(), String> {
+    map_err(delete_profile_internal(&app, state.inner(), id))
+}
+
+#[tauri::command]
+fn get_rules(state: State<AppState>, profile_id: Option<String>) -> Result<Rules, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| profile.current_rules()),
+    )
+}
+
+#[tauri::command]
+fn set_rules(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    rules: Rules,
+) -> Result<(), String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        ensure_distinct_roots(&*state.profiles.lock()?, &profile.id, &rules)?;
+        set_rules_internal(&profile, rules)
+    }))
+}
+
 #[tauri::command]
-fn get_rules(state: State<AppState>) -> Result<Rules, String> {
-    map_err(state.current_rules())
+fn list_rules_versions(
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<Vec<rules::RulesVersion>, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| rules::list_rules_versions(&profile.inner.rules_path)),
+    )
 }
 
 #[tauri::command]
-fn set_rules(state: State<AppState>, rules: Rules) -> Result<(), String> {
-    map_err(set_rules_internal(state.inner(), rules))
+fn restore_rules_version(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    version_id: String,
+) -> Result<Rules, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        restore_rules_version_internal(&app, state.inner(), &profile, &version_id)
+    }))
 }
 
 #[tauri::command]
@@ -123,111 +238,569 @@ fn validate_rules(rules: Rules) -> ValidationResult {
 }
 
 #[tauri::command]
-fn set_sort_root(app: AppHandle, state: State<AppState>, path: String) -> Result<(), String> {
-    map_err(set_sort_root_internal(&app, state.inner(), path))
+fn set_sort_root(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    path: String,
+    migrate: Option<bool>,
+) -> Result<Option<RunResult>, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        ensure_distinct_sort_root(state.inner(), &profile, &path)?;
+        set_sort_root_internal(&app, &profile, path, migrate.unwrap_or(false))
+    }))
+}
+
+#[tauri::command]
+fn preview_sort_root_relocation(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    path: String,
+) -> Result<PlanPreview, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        ensure_distinct_sort_root(state.inner(), &profile, &path)?;
+        relocation_plan(&profile.current_rules()?, &path)
+    }))
+}
+
+#[tauri::command]
+fn dry_run(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    mode: Option<RunMode>,
+) -> Result<PlanPreview, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| dry_run_internal(&profile, mode.unwrap_or_default())),
+    )
+}
+
+#[tauri::command]
+fn explain_classification(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    path: String,
+) -> Result<planner::ClassificationExplanation, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        planner::explain_classification(&profile.current_rules()?, Path::new(&path))
+    }))
+}
+
+#[tauri::command]
+fn preview_rule_impact(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    rules: Rules,
+) -> Result<planner::RuleImpact, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| preview_rule_impact_internal(&profile, &rules)),
+    )
+}
+
+#[tauri::command]
+fn find_duplicates(
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<DuplicateReport, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        duplicates::find_library_duplicates(&profile.current_rules()?)
+    }))
 }
 
 #[tauri::command]
-fn dry_run(state: State<AppState>) -> Result<PlanPreview, String> {
-    map_err(dry_run_internal(state.inner()))
+fn trash_duplicates(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    paths: Vec<String>,
+) -> Result<DuplicateTrashResult, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| trash_duplicates_internal(&profile, &paths)),
+    )
 }
 
 #[tauri::command]
-fn run_now(app: AppHandle, state: State<AppState>) -> Result<RunResult, String> {
-    map_err(run_now_internal(&app, state.inner()))
+fn run_now(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    mode: Option<RunMode>,
+) -> Result<RunResult, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        run_now_internal(&app, &profile, mode.unwrap_or_default(), Pass::OneOff)
+    }))
 }
 
 #[tauri::command]
-fn undo_last_run(app: AppHandle, state: State<AppState>) -> Result<journal::UndoResult, String> {
-    map_err(undo_last_run_internal(&app, state.inner()))
+fn journal_recovery(
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<Vec<journal::RecoveredRun>, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        Ok(profile.inner.recovered_runs.lock()?.clone())
+    }))
 }
 
 #[tauri::command]
-fn start_watcher(app: AppHandle, state: State<AppState>) -> Result<(), String> {
-    map_err(start_watcher_internal(&app, state.inner()))
+fn undo_last_run(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    mode: Option<journal::UndoMode>,
+) -> Result<journal::UndoResult, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        undo_run_internal(&app, &profile, None, mode.unwrap_or_default(), None)
+    }))
 }
 
 #[tauri::command]
-fn stop_watcher(app: AppHandle, state: State<AppState>) -> Result<(), String> {
-    map_err(stop_watcher_internal(&app, state.inner()))
+fn list_journal_runs(
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<Vec<journal::JournalRunSummary>, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| journal::list_runs(&profile.inner.journal_path)),
+    )
 }
 
 #[tauri::command]
-fn watcher_status(state: State<AppState>) -> Result<WatcherStatus, String> {
-    map_err(watcher_status_internal(state.inner()))
+fn undo_run(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    session_id: String,
+    mode: Option<journal::UndoMode>,
+) -> Result<journal::UndoResult, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        undo_run_internal(&app, &profile, Some(&session_id), mode.unwrap_or_default(), None)
+    }))
+}
+
+#[tauri::command]
+fn undo_selected(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    session_id: String,
+    selection: journal::UndoSelection,
+    mode: Option<journal::UndoMode>,
+) -> Result<journal::UndoResult, String> {
+    map_err(state.profile(profile_id.as_deref()).and_then(|profile| {
+        let mode = mode.unwrap_or_default();
+        undo_run_internal(&app, &profile, Some(&session_id), mode, Some(&selection))
+    }))
+}
+
+#[tauri::command]
+fn redo(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    session_id: Option<String>,
+) -> Result<RunResult, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| redo_internal(&app, &profile, session_id.as_deref())),
+    )
+}
+
+#[tauri::command]
+fn start_watcher(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<(), String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| start_watcher_internal(&app, &profile)),
+    )
+}
+
+#[tauri::command]
+fn stop_watcher(
+    app: AppHandle,
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<(), String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| stop_watcher_internal(&app, &profile)),
+    )
+}
+
+#[tauri::command]
+fn watcher_status(
+    state: State<AppState>,
+    profile_id: Option<String>,
+) -> Result<WatcherStatus, String> {
+    map_err(
+        state
+            .profile(profile_id.as_deref())
+            .and_then(|profile| watcher_status_internal(&profile)),
+    )
+}
+
+fn list_profiles_internal(state: &AppState) -> AppResult<Vec<ProfileSummary>> {
+    state
+        .all_profiles()?
+        .iter()
+        .map(profile_summary)
+        .collect()
+}
+
+fn profile_summary(profile: &Profile) -> AppResult<ProfileSummary> {
+    Ok(ProfileSummary {
+        id: profile.id.clone(),
+        sort_root: profile.current_rules()?.global.sort_root,
+        watcher_running: profile.watcher_running()?,
+    })
+}
+
+fn create_profile_internal(
+    state: &AppState,
+    id: String,
+    sort_root: String,
+) -> AppResult<ProfileSummary> {
+    rules::validate_profile_id(&id).map_err(AppError::Validation)?;
+    let mut rules = rules::default_rules();
+    rules.global.sort_root = sort_root;
+
+    // Held from the checks to the insert so two concurrent creates cannot both pass them.
+    let mut profiles = state.profiles.lock()?;
+    if profiles.contains_key(&id) {
+        return Err(AppError::Validation(format!("profile '{}' already exists", id)));
+    }
+    ensure_distinct_roots(&profiles, &id, &rules)?;
+
+    let (rules_path, journal_path) = rules::profile_paths(&id)?;
+    let profile = Profile::new(id.clone(), rules.clone(), rules_path, journal_path);
+    set_rules_internal(&profile, rules)?;
+
+    profiles.insert(id, profile.clone());
+    drop(profiles);
+    profile_summary(&profile)
+}
+
+/// Stops the profile's watcher and sends its config folder (rules and journal) to the trash.
+/// Files already sorted under its sort root are left where they are. The profile is only
+/// dropped once its folder is gone, so a failed trash leaves it fully in place.
+fn delete_profile_internal(app: &AppHandle, state: &AppState, id: String) -> AppResult<()> {
+    if id == rules::DEFAULT_PROFILE_ID {
+        return Err(AppError::Validation(
+            "the default profile cannot be deleted".to_string(),
+        ));
+    }
+
+    let profile = state.profile(Some(&id))?;
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    let watcher_was_running = profile.watcher_running()?;
+    watcher::stop_watcher(&profile.inner.watcher)?;
+
+    if let Some(dir) = profile.inner.rules_path.parent() {
+        if dir.exists() {
+            if let Err(err) = executor::move_to_trash(dir) {
+                if watcher_was_running {
+                    start_watcher_internal(app, &profile)?;
+                }
+                return Err(AppError::State(format!(
+                    "could not trash profile folder '{}': {}",
+                    dir.display(),
+                    err
+                )));
+            }
+        }
+    }
+
+    state.profiles.lock()?.remove(&id);
+    Ok(())
+}
+
+/// Rejects `profile` moving to `sort_root` when that overlaps another profile's roots.
+fn ensure_distinct_sort_root(
+    state: &AppState,
+    profile: &Profile,
+    sort_root: &str,
+) -> AppResult<()> {
+    let mut candidate = profile.current_rules()?;
+    candidate.global.sort_root = sort_root.to_string();
+    ensure_distinct_roots(&*state.profiles.lock()?, &profile.id, &candidate)
+}
+
+/// Rejects rules whose sort root or inbox folders contain, or sit inside, the sort root or an
+/// inbox folder of another profile; two profiles may not sort or drain the same tree.
+fn ensure_distinct_roots(
+    profiles: &BTreeMap<String, Profile>,
+    profile_id: &str,
+    rules: &Rules,
+) -> AppResult<()> {
+    let ours = watched_roots(rules);
+    for other in profiles.values() {
+        if other.id == profile_id {
+            continue;
+        }
+        for theirs in watched_roots(&other.current_rules()?) {
+            if let Some(ours) = ours.iter().find(|ours| rules::sort_roots_overlap(ours, &theirs)) {
+                return Err(AppError::Validation(format!(
+                    "'{}' overlaps '{}' of profile '{}'",
+                    ours, theirs, other.id
+                )));
+            }
+        }
+    }
+    Ok(())
 }
 
-fn set_rules_internal(state: &AppState, rules: Rules) -> AppResult<()> {
+/// The sort root followed by every inbox folder, skipping blank paths.
+fn watched_roots(rules: &Rules) -> Vec<String> {
+    std::iter::once(rules.global.sort_root.as_str())
+        .chain(rules.global.inbox_folders.iter().map(|inbox| inbox.path.as_str()))
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn set_rules_internal(profile: &Profile, rules: Rules) -> AppResult<()> {
     let validation = rules::validate_rules(&rules);
     if !validation.valid {
         return Err(AppError::Validation(validation.errors.join("; ")));
     }
 
-    rules::save_rules(&state.inner.rules_path, &rules)?;
-    state.replace_rules(rules)?;
+    rules::save_rules(&profile.inner.rules_path, &rules)?;
+    profile.replace_rules(rules)?;
     Ok(())
 }
 
-fn set_sort_root_internal(app: &AppHandle, state: &AppState, path: String) -> AppResult<()> {
-    let mut rules = state.current_rules()?;
+/// Points the profile at a new sort root. With `migrate`, the category folders' contents
+/// are moved there first and the move is journaled so `undo_last_run` can reverse it.
+fn set_sort_root_internal(
+    app: &AppHandle,
+    profile: &Profile,
+    path: String,
+    migrate: bool,
+) -> AppResult<Option<RunResult>> {
+    let watcher_was_running = profile.watcher_running()?;
+    if watcher_was_running {
+        stop_watcher_internal(app, profile)?;
+    }
+
+    let result = switch_sort_root(app, profile, path, migrate);
+
+    if watcher_was_running {
+        start_watcher_internal(app, profile)?;
+    }
+    result
+}
+
+/// A migration with errors leaves the sort root unchanged: part of the library is still
+/// under the old root, and the moves that did happen are journaled for undo.
+fn switch_sort_root(
+    app: &AppHandle,
+    profile: &Profile,
+    path: String,
+    migrate: bool,
+) -> AppResult<Option<RunResult>> {
+    let mut rules = profile.current_rules()?;
+    let migration = if migrate {
+        Some(relocate_library(app, profile, &rules, &path)?)
+    } else {
+        None
+    };
+    if migration.as_ref().is_some_and(|result| result.errors > 0) {
+        return Ok(migration);
+    }
+
     rules.global.sort_root = path;
+    set_rules_internal(profile, rules)?;
+    Ok(migration)
+}
+
+fn relocation_plan(rules: &Rules, new_root: &str) -> AppResult<PlanPreview> {
+    let new_root = new_root.trim();
+    if new_root.is_empty() || !Path::new(new_root).is_absolute() {
+        return Err(AppError::Validation(
+            "the new sort root must be an absolute path".to_string(),
+        ));
+    }
+    if rules::sort_roots_overlap(new_root, &rules.global.sort_root) {
+        return Err(AppError::Validation(format!(
+            "cannot migrate between nested sort roots ('{}' and '{}')",
+            rules.global.sort_root, new_root
+        )));
+    }
+    planner::build_relocation_plan(rules, Path::new(new_root))
+}
+
+fn relocate_library(
+    app: &AppHandle,
+    profile: &Profile,
+    rules: &Rules,
+    new_root: &str,
+) -> AppResult<RunResult> {
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    let plan = relocation_plan(rules, new_root)?;
+    std::fs::create_dir_all(new_root.trim())?;
+
+    let mut wal = journal::WriteAhead::begin(
+        &profile.inner.journal_path,
+        &plan.session_id,
+        Some(journal::RELOCATION_RUN_KIND),
+        Some(&rules.global.sort_root),
+    )?;
+    let result = executor::execute_plan(app, &profile.id, &plan, &mut wal)?;
+    journal::append_relocation(
+        &profile.inner.journal_path,
+        &result.session_id,
+        &result.moved_files,
+        &rules.global.sort_root,
+    )?;
+    wal.finish()?;
 
-    set_rules_internal(state, rules)?;
+    if should_emit_run_complete(&result) {
+        let _ = app.emit("run_complete", result.clone());
+    }
+    Ok(result)
+}
 
-    if state.watcher_running()? {
-        stop_watcher_internal(app, state)?;
-        start_watcher_internal(app, state)?;
+/// Makes a saved version current again. The rules being replaced go into the history like
+/// any other save, so a restore can itself be rolled back.
+fn restore_rules_version_internal(
+    app: &AppHandle,
+    state: &AppState,
+    profile: &Profile,
+    version_id: &str,
+) -> AppResult<Rules> {
+    let restored = rules::load_rules_version(&profile.inner.rules_path, version_id)?;
+    ensure_distinct_roots(&*state.profiles.lock()?, &profile.id, &restored)?;
+
+    set_rules_internal(profile, restored.clone())?;
+
+    // The sort root or inbox folders may differ, so re-register the watched paths.
+    if profile.watcher_running()? {
+        stop_watcher_internal(app, profile)?;
+        start_watcher_internal(app, profile)?;
     }
-    Ok(())
+    Ok(restored)
 }
 
-fn dry_run_internal(state: &AppState) -> AppResult<PlanPreview> {
-    let rules = state.current_rules()?;
+fn dry_run_internal(profile: &Profile, mode: RunMode) -> AppResult<PlanPreview> {
+    let rules = profile.current_rules()?;
     rules::ensure_sort_root_dirs(&rules)?;
-    planner::build_plan(&rules)
+    build_plan(profile, &rules, mode, Pass::OneOff)
 }
 
-fn run_now_internal(app: &AppHandle, state: &AppState) -> AppResult<RunResult> {
-    let _guard = RunGuard::acquire(&state.inner.pipeline_running)?;
-    let rules = state.current_rules()?;
+fn preview_rule_impact_internal(profile: &Profile, candidate: &Rules) -> AppResult<planner::RuleImpact> {
+    let validation = rules::validate_rules(candidate);
+    if !validation.valid {
+        return Err(AppError::Validation(validation.errors.join("; ")));
+    }
+    planner::rule_impact(&profile.current_rules()?, candidate)
+}
+
+fn trash_duplicates_internal(profile: &Profile, paths: &[String]) -> AppResult<DuplicateTrashResult> {
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    duplicates::trash_duplicates(&profile.current_rules()?, &profile.inner.journal_path, paths)
+}
+
+/// What started a sort pass.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    /// A dry run or Run now.
+    OneOff,
+    /// The watcher, after file events.
+    Watcher,
+    /// The watcher, retrying files an earlier pass held back.
+    WatcherRetry,
+}
+
+/// Only watcher passes read and replace the profile's size samples; a dry run or Run now is
+/// a one-off pass that must not change what the next watcher pass decides.
+fn build_plan(
+    profile: &Profile,
+    rules: &Rules,
+    mode: RunMode,
+    pass: Pass,
+) -> AppResult<PlanPreview> {
+    match mode {
+        RunMode::Sort if pass != Pass::OneOff => {
+            let mut samples = profile.inner.size_samples.lock()?;
+            planner::build_plan_with_samples(rules, &mut samples)
+        }
+        RunMode::Sort => planner::build_plan(rules),
+        RunMode::Library => {
+            planner::build_library_plan(rules, &rules::protected_top_level_folders(rules))
+        }
+    }
+}
+
+fn run_now_internal(
+    app: &AppHandle,
+    profile: &Profile,
+    mode: RunMode,
+    pass: Pass,
+) -> AppResult<RunResult> {
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    let rules = profile.current_rules()?;
 
     rules::ensure_sort_root_dirs(&rules)?;
 
-    let plan = planner::build_plan(&rules)?;
-    let mut result = executor::execute_plan(app, &plan)?;
+    let plan = build_plan(profile, &rules, mode, pass)?;
+    let mut wal =
+        journal::WriteAhead::begin(&profile.inner.journal_path, &plan.session_id, None, None)?;
+    let mut result = executor::execute_plan(app, &profile.id, &plan, &mut wal)?;
 
     if rules.global.cleanup_empty_folders.enabled {
         let cleanup_result = cleanup::cleanup_empty_folders(&rules)?;
         apply_cleanup(&mut result, cleanup_result);
     }
 
-    let overrides = resolve_original_path_overrides(state, &result.moved_files)?;
+    let overrides = resolve_original_path_overrides(profile, &result.moved_files)?;
     journal::append_run(
-        &state.inner.journal_path,
+        &profile.inner.journal_path,
         &result.session_id,
         &result.moved_files,
         &overrides,
     )?;
-    clear_origin_hints(state)?;
+    wal.finish()?;
+    clear_origin_hints(profile)?;
 
-    if should_emit_run_complete(&result) {
+    // A retry that moved nothing only repeats the skips the UI has already been told about.
+    let idle_retry = pass == Pass::WatcherRetry && result.moved == 0;
+    if should_emit_run_complete(&result) && !idle_retry {
         let _ = app.emit("run_complete", result.clone());
     }
     Ok(result)
 }
 
-fn undo_last_run_internal(app: &AppHandle, state: &AppState) -> AppResult<journal::UndoResult> {
-    let _guard = RunGuard::acquire(&state.inner.pipeline_running)?;
-    let _undo_guard = BoolGuard::set(&state.inner.undo_in_progress, true);
-    let watcher_was_running = state.watcher_running()?;
+/// Undoes the run `session_id`, or the newest run when it is `None`. With a `selection`
+/// only the matching moves are restored.
+fn undo_run_internal(
+    app: &AppHandle,
+    profile: &Profile,
+    session_id: Option<&str>,
+    mode: journal::UndoMode,
+    selection: Option<&journal::UndoSelection>,
+) -> AppResult<journal::UndoResult> {
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    let _undo_guard = BoolGuard::set(&profile.inner.undo_in_progress, true);
+    let watcher_was_running = profile.watcher_running()?;
 
     if watcher_was_running {
-        stop_watcher_internal(app, state)?;
+        stop_watcher_internal(app, profile)?;
     }
 
     // Grab sort_root from in-memory rules
     let sort_root = {
-        let rules = state
+        let rules = profile
             .inner
             .rules
             .lock()
@@ -241,13 +814,44 @@ fn undo_last_run_internal(app: &AppHandle, state: &AppState) -> AppResult<journa
         ));
     }
 
+    let journal_path = &profile.inner.journal_path;
+    let target = match session_id {
+        Some(id) => journal::load_run(journal_path, id)?,
+        None => journal::load_last_run(journal_path)?,
+    };
+    let relocation = target
+        .as_ref()
+        .filter(|run| run.kind.as_deref() == Some(journal::RELOCATION_RUN_KIND))
+        .and_then(|run| Some((run.session_id.clone(), run.previous_sort_root.clone()?)));
+
     // ✅ ONE `?` only: this returns UndoResult directly.
-    let result =
-        journal::undo_last_run(&state.inner.journal_path, Path::new(sort_root.as_str()))?;
+    let sort_root = Path::new(sort_root.as_str());
+    let result = match (session_id, selection) {
+        (Some(id), Some(selection)) => {
+            journal::undo_selected(journal_path, id, sort_root, mode, selection)?
+        }
+        (Some(id), None) => journal::undo_run(journal_path, id, sort_root, mode)?,
+        (None, _) => journal::undo_last_run(journal_path, sort_root, mode)?,
+    };
+
+    // Once every move of a relocation is undone the library is back under the old root, so
+    // the profile follows it. A partial undo leaves the root where the rest of it still is.
+    if let Some((relocation_id, previous)) = relocation {
+        let fully_undone = journal::load_run(journal_path, &relocation_id)?.is_some_and(|run| {
+            run.moves
+                .iter()
+                .all(|movement| movement.status == journal::UNDONE_STATUS)
+        });
+        if fully_undone {
+            let mut rules = profile.current_rules()?;
+            rules.global.sort_root = previous;
+            set_rules_internal(profile, rules)?;
+        }
+    }
 
     if watcher_was_running {
         std::thread::sleep(Duration::from_millis(1500));
-        start_watcher_internal(app, state)?;
+        start_watcher_internal(app, profile)?;
     }
 
     executor::emit_log(
@@ -262,75 +866,151 @@ fn undo_last_run_internal(app: &AppHandle, state: &AppState) -> AppResult<journa
     Ok(result)
 }
 
-fn start_watcher_internal(app: &AppHandle, state: &AppState) -> AppResult<()> {
-    let rules = state.current_rules()?;
+/// Re-applies an undo (see `journal::load_redo_source`): restored files go back to the
+/// paths the undone run had moved them to, under the current collision policy. Like undo,
+/// it pauses the watcher so the files it moves are not sorted mid-redo.
+fn redo_internal(
+    app: &AppHandle,
+    profile: &Profile,
+    session_id: Option<&str>,
+) -> AppResult<RunResult> {
+    let _guard = RunGuard::acquire(&profile.inner.pipeline_running)?;
+    let _undo_guard = BoolGuard::set(&profile.inner.undo_in_progress, true);
+    let watcher_was_running = profile.watcher_running()?;
+
+    if watcher_was_running {
+        stop_watcher_internal(app, profile)?;
+    }
+
+    let result = redo_moves(app, profile, session_id);
+
+    if watcher_was_running {
+        std::thread::sleep(Duration::from_millis(1500));
+        start_watcher_internal(app, profile)?;
+    }
+
+    let result = result?;
+    if should_emit_run_complete(&result) {
+        let _ = app.emit("run_complete", result.clone());
+    }
+    Ok(result)
+}
+
+fn redo_moves(
+    app: &AppHandle,
+    profile: &Profile,
+    session_id: Option<&str>,
+) -> AppResult<RunResult> {
+    let rules = profile.current_rules()?;
+    let journal_path = &profile.inner.journal_path;
+
+    let undo_entry = journal::load_redo_source(journal_path, session_id)?;
+    let candidates: Vec<_> = journal::redo_candidates(&undo_entry).collect();
+    let plan = planner::build_redo_plan(&rules, &candidates);
+
+    let mut wal =
+        journal::WriteAhead::begin_redo(journal_path, &plan.session_id, &undo_entry.session_id)?;
+    let result = executor::execute_plan(app, &profile.id, &plan, &mut wal)?;
+    journal::append_redo(journal_path, &result.session_id, &result.moved_files, &undo_entry)?;
+    wal.finish()?;
+    Ok(result)
+}
+
+fn start_watcher_internal(app: &AppHandle, profile: &Profile) -> AppResult<()> {
+    let rules = profile.current_rules()?;
     rules::ensure_sort_root_dirs(&rules)?;
 
     let sort_root = PathBuf::from(&rules.global.sort_root);
     let app_handle = app.clone();
-    let state_clone = state.clone();
+    let profile_clone = profile.clone();
 
-    let action: DebouncedAction = Arc::new(move || {
-        if state_clone.inner.undo_in_progress.load(Ordering::SeqCst) {
-            return;
+    let action: DebouncedAction = Arc::new(move |retry| {
+        if profile_clone.inner.undo_in_progress.load(Ordering::SeqCst) {
+            return false;
         }
 
-        if let Err(err) = prune_origin_hints(&state_clone) {
+        if let Err(err) = prune_origin_hints(&profile_clone) {
             executor::emit_log(&app_handle, "warn", format!("prune_origin_hints failed: {}", err));
         }
 
-        if let Err(err) = run_now_internal(&app_handle, &state_clone) {
-            executor::emit_log(
-                &app_handle,
-                "error",
-                format!("watcher-triggered run failed: {}", err),
-            );
+        let pass = if retry { Pass::WatcherRetry } else { Pass::Watcher };
+        match run_now_internal(&app_handle, &profile_clone, RunMode::Sort, pass) {
+            Ok(result) => result.pending > 0,
+            Err(err) => {
+                executor::emit_log(
+                    &app_handle,
+                    "error",
+                    format!("watcher-triggered run failed: {}", err),
+                );
+                false
+            }
         }
     });
 
-    let hint_state = state.clone();
+    let hint_profile = profile.clone();
     let hint_sort_root = sort_root.clone();
     let observer: EventObserver = Arc::new(move |event| {
-        capture_origin_hint(&hint_state, &hint_sort_root, event);
+        capture_origin_hint(&hint_profile, &hint_sort_root, event);
     });
 
+    let mut watch_paths = vec![(sort_root, true)];
+    for inbox in &rules.global.inbox_folders {
+        let path = PathBuf::from(inbox.path.trim());
+        if path.is_dir() {
+            watch_paths.push((path, inbox.recursive));
+        } else {
+            executor::emit_log(
+                app,
+                "warn",
+                format!("inbox folder '{}' does not exist; not watching it", inbox.path),
+            );
+        }
+    }
+
     watcher::start_watcher(
-        &state.inner.watcher,
-        sort_root,
+        &profile.inner.watcher,
+        watch_paths,
         Duration::from_secs(2),
         action,
         Some(observer),
     )?;
 
-    emit_watcher_status(app, state)
+    emit_watcher_status(app, profile)
 }
 
-fn stop_watcher_internal(app: &AppHandle, state: &AppState) -> AppResult<()> {
-    watcher::stop_watcher(&state.inner.watcher)?;
-    emit_watcher_status(app, state)
+fn stop_watcher_internal(app: &AppHandle, profile: &Profile) -> AppResult<()> {
+    watcher::stop_watcher(&profile.inner.watcher)?;
+    emit_watcher_status(app, profile)
 }
 
-fn watcher_status_internal(state: &AppState) -> AppResult<WatcherStatus> {
-    let rules = state.current_rules()?;
-    let running = state.watcher_running()?;
+fn watcher_status_internal(profile: &Profile) -> AppResult<WatcherStatus> {
+    let rules = profile.current_rules()?;
+    let running = profile.watcher_running()?;
     Ok(WatcherStatus {
+        profile_id: profile.id.clone(),
         running,
         sort_root: rules.global.sort_root,
+        inbox_folders: rules
+            .global
+            .inbox_folders
+            .into_iter()
+            .map(|inbox| inbox.path)
+            .collect(),
     })
 }
 
-fn emit_watcher_status(app: &AppHandle, state: &AppState) -> AppResult<()> {
-    let status = watcher_status_internal(state)?;
+fn emit_watcher_status(app: &AppHandle, profile: &Profile) -> AppResult<()> {
+    let status = watcher_status_internal(profile)?;
     let _ = app.emit("watcher_status", status);
     Ok(())
 }
 
-fn clear_origin_hints(state: &AppState) -> AppResult<()> {
-    state.inner.origin_hints.lock()?.clear();
+fn clear_origin_hints(profile: &Profile) -> AppResult<()> {
+    profile.inner.origin_hints.lock()?.clear();
     Ok(())
 }
 
-fn capture_origin_hint(state: &AppState, sort_root: &Path, event: &notify::Event) {
+fn capture_origin_hint(profile: &Profile, sort_root: &Path, event: &notify::Event) {
     if event.paths.len() < 2 {
         return;
     }
@@ -341,7 +1021,7 @@ fn capture_origin_hint(state: &AppState, sort_root: &Path, event: &notify::Event
     let from_inside = from.starts_with(sort_root);
     let to_inside = to.starts_with(sort_root);
 
-    let Ok(mut hints) = state.inner.origin_hints.lock() else {
+    let Ok(mut hints) = profile.inner.origin_hints.lock() else {
         return;
     };
 
@@ -390,10 +1070,10 @@ fn capture_origin_hint(state: &AppState, sort_root: &Path, event: &notify::Event
 }
 
 fn resolve_original_path_overrides(
-    state: &AppState,
+    profile: &Profile,
     moved_files: &[executor::MovedFile],
 ) -> AppResult<HashMap<String, String>> {
-    let mut hints = state.inner.origin_hints.lock()?.clone();
+    let mut hints = profile.inner.origin_hints.lock()?.clone();
     hints.sort_by(|left, right| {
         right
             .observed_path
@@ -436,8 +1116,8 @@ fn resolve_original_path_overrides(
     Ok(overrides)
 }
 
-fn prune_origin_hints(state: &AppState) -> AppResult<()> {
-    let mut hints = state.inner.origin_hints.lock()?;
+fn prune_origin_hints(profile: &Profile) -> AppResult<()> {
+    let mut hints = profile.inner.origin_hints.lock()?;
     hints.retain(|entry| entry.observed_path.exists());
     Ok(())
 }
@@ -470,21 +1150,44 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
-            let rules_path = rules::rules_path()?;
-            let journal_path = rules::journal_path()?;
-            let rules = rules::load_or_create_rules(&rules_path)?;
+            let mut profiles = Vec::new();
+            for id in rules::list_profile_ids()? {
+                let (rules_path, journal_path) = rules::profile_paths(&id)?;
+                let rules = rules::load_or_create_rules(&rules_path)?;
+                // An unreadable journal must not keep the app from starting.
+                let recovered = journal::recover_unfinished_runs(&journal_path)
+                    .unwrap_or_else(|err| vec![journal::RecoveredRun::failed(&journal_path, &err)]);
+                let profile = Profile::new(id, rules, rules_path, journal_path);
+                profile.set_recovered_runs(recovered)?;
+                profiles.push(profile);
+            }
 
-            app.manage(AppState::new(rules, rules_path, journal_path));
+            app.manage(AppState::new(profiles));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            list_profiles,
+            create_profile,
+            delete_profile,
             get_rules,
             set_rules,
+            list_rules_versions,
+            restore_rules_version,
             validate_rules,
             set_sort_root,
+            preview_sort_root_relocation,
             dry_run,
+            explain_classification,
+            preview_rule_impact,
+            find_duplicates,
+            trash_duplicates,
             run_now,
+            journal_recovery,
             undo_last_run,
+            list_journal_runs,
+            undo_run,
+            undo_selected,
+            redo,
             start_watcher,
             stop_watcher,
             watcher_status,
@@ -509,6 +1212,7 @@ mod acceptance_tests {
         let mut rules = rules::default_rules();
         rules.global.sort_root = root.to_string_lossy().to_string();
         rules.global.min_file_age_seconds = 0;
+        rules.global.require_stable_size = false;
         rules.global.cleanup_empty_folders.enabled = true;
         rules.global.cleanup_empty_folders.min_age_seconds = 0;
         rules
@@ -583,99 +1287,1461 @@ mod acceptance_tests {
     }
 
     #[test]
-    fn acceptance_cleanup_protects_category_and_root() {
+    fn acceptance_filename_patterns_take_precedence_over_extensions() {
         let root = temp_sort_root();
-        let rules = make_rules(&root);
+        let mut rules = make_rules(&root);
+        rules.categories.insert(
+            0,
+            rules::CategoryRule {
+                id: "screenshots".to_string(),
+                name: "Screenshots".to_string(),
+                target_subfolder: "Screenshots".to_string(),
+                extensions: Vec::new(),
+                filename_globs: vec!["Screenshot *.png".to_string()],
+                filename_regexes: Vec::new(),
+            },
+        );
+        rules.categories.push(rules::CategoryRule {
+            id: "invoices".to_string(),
+            name: "Invoices".to_string(),
+            target_subfolder: "Invoices".to_string(),
+            extensions: Vec::new(),
+            filename_globs: Vec::new(),
+            filename_regexes: vec![r"^invoice-\d+\.pdf$".to_string()],
+        });
+        assert!(rules::validate_rules(&rules).valid);
         rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
 
-        write_file(&root.join("Incoming/Nested/clip.mp4"), b"video");
+        write_file(&root.join("Drop/Screenshot 2026-01-02.PNG"), b"img");
+        write_file(&root.join("Drop/holiday.png"), b"img");
+        write_file(&root.join("Drop/invoice-0042.pdf"), b"pdf");
+        write_file(&root.join("Drop/manual.pdf"), b"pdf");
 
         let plan = planner::build_plan(&rules).expect("build plan");
-        let run = executor::execute_plan_silent(&plan).expect("execute plan");
-        assert_eq!(run.errors, 0);
-
-        let cleanup = cleanup::cleanup_empty_folders(&rules).expect("cleanup");
-        assert_eq!(cleanup.errors, 0);
+        let categories: HashMap<_, _> = plan
+            .moves
+            .iter()
+            .map(|entry| {
+                let name = Path::new(&entry.source_path)
+                    .file_name()
+                    .expect("file name")
+                    .to_string_lossy()
+                    .to_string();
+                (name, entry.category.clone())
+            })
+            .collect();
 
-        assert!(root.exists());
-        for folder in [
-            "Documents",
-            "Images",
-            "Video",
-            "Audio",
-            "Archives",
-            "Code",
-            "Executables",
-            "Data",
-            "Misc",
-        ] {
-            assert!(root.join(folder).exists(), "protected folder missing: {}", folder);
-        }
+        assert_eq!(categories["Screenshot 2026-01-02.PNG"], "Screenshots");
+        assert_eq!(categories["holiday.png"], "Images");
+        assert_eq!(categories["invoice-0042.pdf"], "Invoices");
+        assert_eq!(categories["manual.pdf"], "Documents");
 
-        assert!(!root.join("Incoming/Nested").exists());
+        rules.categories[0].filename_regexes = vec!["(unclosed".to_string()];
+        assert!(!rules::validate_rules(&rules).valid);
 
         tear_down(&root);
     }
 
     #[test]
-    fn acceptance_dry_run_matches_run_destinations() {
+    fn acceptance_content_sniffing_fallback_and_override() {
         let root = temp_sort_root();
-        let rules = make_rules(&root);
+        let mut rules = make_rules(&root);
         rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
 
-        write_file(&root.join("Drop/img.png"), b"img");
-        write_file(&root.join("Drop/movie.mkv"), b"video");
-        write_file(&root.join("Drop/archive.zip"), b"zip");
+        write_file(&root.join("Drop/scan"), b"\x89PNG\r\n\x1a\nrest");
+        write_file(&root.join("Drop/report.bin"), b"%PDF-1.7 body");
+        write_file(&root.join("Drop/letter.docx"), b"PK\x03\x04body");
 
+        let destination_of = |plan: &planner::PlanPreview, name: &str| {
+            plan.moves
+                .iter()
+                .find(|entry| entry.source_path.ends_with(name))
+                .map(|entry| (entry.category.clone(), entry.detected_type.clone()))
+                .expect("planned entry")
+        };
+
+        rules.global.content_sniffing = rules::ContentSniffing::Fallback;
         let plan = planner::build_plan(&rules).expect("build plan");
-        let expected: BTreeSet<_> = plan
-            .moves
-            .iter()
-            .map(|entry| entry.destination_path.clone())
-            .collect();
+        assert_eq!(destination_of(&plan, "scan"), ("Images".to_string(), Some("png".to_string())));
+        assert_eq!(destination_of(&plan, "report.bin"), ("Documents".to_string(), Some("pdf".to_string())));
+        assert_eq!(destination_of(&plan, "letter.docx"), ("Documents".to_string(), None));
+
+        rules.global.content_sniffing = rules::ContentSniffing::Override;
+        let plan = planner::build_plan(&rules).expect("build plan");
+        assert_eq!(destination_of(&plan, "letter.docx"), ("Documents".to_string(), Some("zip".to_string())));
+
+        rules.global.content_sniffing = rules::ContentSniffing::Off;
+        let plan = planner::build_plan(&rules).expect("build plan");
+        assert_eq!(destination_of(&plan, "scan"), ("Misc".to_string(), None));
+        assert_eq!(destination_of(&plan, "report.bin"), ("Misc".to_string(), None));
 
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_destination_templates_expand_per_file() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        for category in &mut rules.categories {
+            match category.id.as_str() {
+                "images" => category.target_subfolder = "Images/{mtime:%Y}/{mtime:%m}".to_string(),
+                "archives" => category.target_subfolder = "Archives/{ext}".to_string(),
+                _ => {}
+            }
+        }
+        assert!(rules::validate_rules(&rules).valid);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+        assert!(root.join("Images").is_dir());
+        assert!(!root.join("Images/{mtime:%Y}").exists());
+
+        let photo = root.join("Drop/photo.jpg");
+        write_file(&photo, b"img");
+        write_file(&root.join("Drop/backup.zip"), b"zip");
+        let modified: chrono::DateTime<chrono::Local> = fs::metadata(&photo)
+            .and_then(|meta| meta.modified())
+            .expect("mtime")
+            .into();
+
+        let plan = planner::build_plan(&rules).expect("build plan");
         let run = executor::execute_plan_silent(&plan).expect("execute plan");
         assert_eq!(run.errors, 0);
-        assert_eq!(run.moved, plan.move_count);
-        assert_eq!(run.skipped, plan.skip_count);
 
-        let actual: BTreeSet<_> = run
-            .moved_files
-            .iter()
-            .map(|entry| entry.destination_path.clone())
-            .collect();
+        let expected_photo = root
+            .join("Images")
+            .join(modified.format("%Y").to_string())
+            .join(modified.format("%m").to_string())
+            .join("photo.jpg");
+        assert!(expected_photo.exists());
+        assert!(root.join("Archives/zip/backup.zip").exists());
 
-        assert_eq!(actual, expected);
+        rules.categories[0].target_subfolder = "Documents/{unknown}".to_string();
+        assert!(!rules::validate_rules(&rules).valid);
+        rules.categories[0].target_subfolder = "Documents/../../escape".to_string();
+        assert!(!rules::validate_rules(&rules).valid);
 
         tear_down(&root);
     }
 
     #[test]
-    fn acceptance_undo_last_run_best_effort() {
+    fn acceptance_compound_extensions_match_longest_suffix() {
         let root = temp_sort_root();
         let mut rules = make_rules(&root);
-        rules.global.cleanup_empty_folders.enabled = false;
+        rules.categories.push(rules::CategoryRule {
+            id: "userscripts".to_string(),
+            name: "Userscripts".to_string(),
+            target_subfolder: "Userscripts".to_string(),
+            extensions: vec![".user.js".to_string()],
+            filename_globs: Vec::new(),
+            filename_regexes: Vec::new(),
+        });
         rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
 
-        write_file(&root.join("Load/invoice.txt"), b"doc");
-        write_file(&root.join("Load/song.mp3"), b"audio");
+        write_file(&root.join("Archives/backup.tar.gz"), b"existing");
+        write_file(&root.join("Drop/backup.tar.gz"), b"incoming");
+        write_file(&root.join("Drop/dark-mode.user.js"), b"script");
+        write_file(&root.join("Drop/app.js"), b"code");
 
         let plan = planner::build_plan(&rules).expect("build plan");
         let run = executor::execute_plan_silent(&plan).expect("execute plan");
         assert_eq!(run.errors, 0);
-        assert!(run.moved >= 2);
 
-        let journal_path = root.join("journal.jsonl");
-        let overrides: HashMap<String, String> = HashMap::new();
-        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &overrides)
-            .expect("append run");
+        assert!(root.join("Archives/backup (1).tar.gz").exists());
+        assert!(root.join("Userscripts/dark-mode.user.js").exists());
+        assert!(root.join("Code/app.js").exists());
 
-        let undo = journal::undo_last_run(&journal_path, root.as_path()).expect("undo last run");
+        tear_down(&root);
+    }
 
-        assert!(undo.restored >= 1);
+    #[test]
+    fn acceptance_sort_modes_preserve_or_group_dropped_trees() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("SomeDump/A/B/song.mp3"), b"audio");
+        write_file(&root.join("loose.txt"), b"doc");
+
+        let destinations = |rules: &rules::Rules| -> BTreeSet<PathBuf> {
+            planner::build_plan(rules)
+                .expect("build plan")
+                .moves
+                .iter()
+                .map(|entry| PathBuf::from(&entry.destination_path))
+                .collect()
+        };
+
+        rules.global.sort_mode = rules::SortMode::Preserve;
+        let preserved = destinations(&rules);
+        assert!(preserved.contains(&root.join("Audio/A/B/song.mp3")));
+        assert!(preserved.contains(&root.join("Documents/loose.txt")));
+
+        rules.global.sort_mode = rules::SortMode::GroupByDrop;
+        let grouped = destinations(&rules);
+        assert!(grouped.contains(&root.join("Audio/SomeDump/song.mp3")));
+        assert!(grouped.contains(&root.join("Documents/loose.txt")));
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert!(root.join("Audio/SomeDump/song.mp3").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_bundles_move_intact_and_undo() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Drop/myrepo/.git/HEAD"), b"ref: refs/heads/main");
+        write_file(&root.join("Drop/myrepo/src/main.rs"), b"fn main() {}");
+        write_file(&root.join("Drop/myrepo/logo.png"), b"img");
+        write_file(&root.join("Drop/Tool.app/Contents/Info.plist"), b"plist");
+        write_file(&root.join("Drop/pic.png"), b"img");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        assert_eq!(plan.move_count, 3);
+        let bundles: BTreeSet<_> = plan.moves.iter().filter_map(|entry| entry.bundle.clone()).collect();
+        assert!(bundles.contains("Projects"));
+        assert!(bundles.contains("App bundles"));
+
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert!(root.join("Code/myrepo/.git/HEAD").exists());
+        assert!(root.join("Code/myrepo/logo.png").exists());
+        assert!(root.join("Executables/Tool.app/Contents/Info.plist").exists());
+        assert!(root.join("Images/pic.png").exists());
+
+        let journal_path = root.join("journal.jsonl");
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo last run");
+        assert_eq!(undo.errors, 0);
+        assert_eq!(undo.restored, 3);
+        assert!(!root.join("Code/myrepo").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_sortignore_and_global_excludes_are_skipped() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.exclude_patterns = vec!["*.tmp".to_string(), "/Inbox-Hold/".to_string()];
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Drop/.sortignore"), b"drafts/\n*.txt\n!keep.txt\n");
+        write_file(&root.join("Drop/drafts/plan.pdf"), b"pdf");
+        write_file(&root.join("Drop/notes.txt"), b"doc");
+        write_file(&root.join("Drop/keep.txt"), b"doc");
+        write_file(&root.join("Drop/scratch.tmp"), b"tmp");
+        write_file(&root.join("Inbox-Hold/song.mp3"), b"audio");
+        write_file(&root.join("Drop/song.mp3"), b"audio");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let moved: BTreeSet<_> = plan
+            .moves
+            .iter()
+            .map(|entry| PathBuf::from(&entry.source_path))
+            .collect();
+        assert_eq!(
+            moved,
+            BTreeSet::from([root.join("Drop/keep.txt"), root.join("Drop/song.mp3")])
+        );
+
+        let reason_for = |path: PathBuf| {
+            plan.skips
+                .iter()
+                .find(|skip| Path::new(&skip.path) == path)
+                .map(|skip| skip.reason.clone())
+                .expect("skip entry")
+        };
+        assert!(reason_for(root.join("Drop/drafts")).contains("'drafts/'"));
+        assert!(reason_for(root.join("Drop/notes.txt")).contains("'*.txt'"));
+        assert!(reason_for(root.join("Drop/scratch.tmp")).contains("excludePatterns"));
+        assert!(reason_for(root.join("Inbox-Hold")).contains("'/Inbox-Hold/'"));
+
+        rules.global.exclude_patterns.push("[z-a].txt".to_string());
+        assert!(!rules::validate_rules(&rules).valid);
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_in_progress_downloads_and_unstable_sizes_are_held_back() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.require_stable_size = true;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Drop/movie.mkv.crdownload"), b"partial");
+        write_file(&root.join("Drop/seed.iso.!qB"), b"partial");
+        write_file(&root.join("Drop/~$budget.xlsx"), b"lock");
+        write_file(&root.join("Drop/report.pdf"), b"pdf");
+
+        let mut samples = planner::SizeSamples::new();
+        let first = planner::build_plan_with_samples(&rules, &mut samples).expect("first pass");
+        assert_eq!(first.move_count, 0);
+        // Only report.pdf is pending; in-progress names wait for their own rename event.
+        assert_eq!(first.pending_count, 1);
+        assert!(first
+            .skips
+            .iter()
+            .any(|skip| skip.path.ends_with("seed.iso.!qB") && skip.reason.contains("'*.!qB'")));
+
+        write_file(&root.join("Drop/report.pdf"), b"pdf grew");
+        let second = planner::build_plan_with_samples(&rules, &mut samples).expect("second pass");
+        assert_eq!(second.move_count, 0);
+
+        let third = planner::build_plan_with_samples(&rules, &mut samples).expect("third pass");
+        assert_eq!(third.move_count, 1);
+        assert!(third.moves[0].source_path.ends_with("report.pdf"));
+        assert_eq!(third.pending_count, 0);
+        assert_eq!(third.skip_count, 3);
+
+        write_file(&root.join("Drop/video.mp4.part"), b"partial");
+        let partial = planner::build_plan_with_samples(&rules, &mut samples).expect("part pass");
+        assert_eq!(partial.pending_count, 0);
+        assert!(partial
+            .skips
+            .iter()
+            .any(|skip| skip.path.ends_with("video.mp4.part") && skip.reason.contains("'*.part'")));
+
+        // A one-off pass has no earlier sample; the file only has to be left alone briefly.
+        write_file(&root.join("Drop/notes.txt"), b"notes");
+        let fresh = planner::build_plan(&rules).expect("fresh one-off pass");
+        assert!(fresh
+            .skips
+            .iter()
+            .any(|skip| skip.path.ends_with("notes.txt") && skip.reason.contains("stabilise")));
+        let earlier = std::time::SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(root.join("Drop/notes.txt"))
+            .and_then(|file| file.set_modified(earlier))
+            .expect("age notes.txt");
+        let settled = planner::build_plan(&rules).expect("settled one-off pass");
+        assert!(settled.moves.iter().any(|entry| entry.source_path.ends_with("notes.txt")));
+
+        tear_down(&root);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn acceptance_files_open_for_writing_are_skipped() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        let busy = root.join("Drop/busy.txt");
+        write_file(&busy, b"partial");
+        write_file(&root.join("Drop/done.txt"), b"done");
+        let handle = fs::OpenOptions::new().append(true).open(&busy).expect("open for write");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        assert_eq!(plan.move_count, 1);
+        let skip = plan
+            .skips
+            .iter()
+            .find(|skip| Path::new(&skip.path) == busy)
+            .expect("busy file skipped");
+        assert!(skip.reason.starts_with(&format!("in use by {}/", std::process::id())));
+
+        // `/proc` reports resolved paths; a symlinked sort root must still match them.
+        let link = root.with_extension("link");
+        std::os::unix::fs::symlink(&root, &link).expect("symlink sort root");
+        let mut linked = rules.clone();
+        linked.global.sort_root = link.to_string_lossy().to_string();
+        let plan = planner::build_plan(&linked).expect("build linked plan");
+        let _ = fs::remove_file(&link);
+        assert_eq!(plan.move_count, 1);
+        assert!(plan.skips.iter().any(|skip| skip.reason.starts_with("in use by")));
+
+        rules.global.skip_open_files = false;
+        assert_eq!(planner::build_plan(&rules).expect("build plan").move_count, 2);
+
+        drop(handle);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_cleanup_leaves_excluded_empty_folders() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.exclude_patterns = vec!["/Keep/".to_string()];
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        for folder in ["Keep", "Projects/Placeholder", "Old/Empty"] {
+            fs::create_dir_all(root.join(folder)).expect("create folder");
+        }
+        write_file(&root.join("Projects/.sortignore"), b"Placeholder/\n");
+
+        let cleanup = cleanup::cleanup_empty_folders(&rules).expect("cleanup");
+        assert_eq!(cleanup.errors, 0);
+        assert_eq!(cleanup.trashed, 2);
+        assert!(root.join("Keep").is_dir());
+        assert!(root.join("Projects/Placeholder").is_dir());
+        assert!(!root.join("Old").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_cleanup_protects_category_and_root() {
+        let root = temp_sort_root();
+        let rules = make_rules(&root);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Incoming/Nested/clip.mp4"), b"video");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+
+        let cleanup = cleanup::cleanup_empty_folders(&rules).expect("cleanup");
+        assert_eq!(cleanup.errors, 0);
+
+        assert!(root.exists());
+        for folder in [
+            "Documents",
+            "Images",
+            "Video",
+            "Audio",
+            "Archives",
+            "Code",
+            "Executables",
+            "Data",
+            "Misc",
+        ] {
+            assert!(root.join(folder).exists(), "protected folder missing: {}", folder);
+        }
+
+        assert!(!root.join("Incoming/Nested").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_dry_run_matches_run_destinations() {
+        let root = temp_sort_root();
+        let rules = make_rules(&root);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Drop/img.png"), b"img");
+        write_file(&root.join("Drop/movie.mkv"), b"video");
+        write_file(&root.join("Drop/archive.zip"), b"zip");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let expected: BTreeSet<_> = plan
+            .moves
+            .iter()
+            .map(|entry| entry.destination_path.clone())
+            .collect();
+
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert_eq!(run.moved, plan.move_count);
+        assert_eq!(run.skipped, plan.skip_count);
+
+        let actual: BTreeSet<_> = run
+            .moved_files
+            .iter()
+            .map(|entry| entry.destination_path.clone())
+            .collect();
+
+        assert_eq!(actual, expected);
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_undo_last_run_best_effort() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Load/invoice.txt"), b"doc");
+        write_file(&root.join("Load/song.mp3"), b"audio");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert!(run.moved >= 2);
+
+        let journal_path = root.join("journal.jsonl");
+        let overrides: HashMap<String, String> = HashMap::new();
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &overrides)
+            .expect("append run");
+
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo last run");
+
+        assert!(undo.restored >= 1);
         assert_eq!(undo.errors, 0);
         assert!(root.join("Restored").join(&run.session_id).exists());
 
         tear_down(&root);
     }
+
+    #[test]
+    fn acceptance_undo_any_run_by_session_id() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+        let journal_path = root.with_extension("journal.jsonl");
+        let overrides: HashMap<String, String> = HashMap::new();
+        let mode = journal::UndoMode::RestoredFolder;
+
+        write_file(&root.join("Load/invoice.txt"), b"doc");
+        let plan = planner::build_plan(&rules).expect("build first plan");
+        let first = executor::execute_plan_silent(&plan).expect("execute first plan");
+        journal::append_run(&journal_path, &first.session_id, &first.moved_files, &overrides)
+            .expect("append first run");
+
+        write_file(&root.join("Load/song.mp3"), b"audio");
+        let plan = planner::build_plan(&rules).expect("build second plan");
+        let second = executor::execute_plan_silent(&plan).expect("execute second plan");
+        journal::append_run(&journal_path, &second.session_id, &second.moved_files, &overrides)
+            .expect("append second run");
+
+        let undo = journal::undo_run(&journal_path, &first.session_id, root.as_path(), mode)
+            .expect("undo older run");
+        assert_eq!(undo.session_id.as_deref(), Some(first.session_id.as_str()));
+        assert_eq!(undo.restored, 1);
+        assert!(!Path::new(&first.moved_files[0].destination_path).exists());
+        assert!(Path::new(&second.moved_files[0].destination_path).exists());
+
+        let runs = journal::list_runs(&journal_path).expect("list runs");
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].kind.as_deref(), Some(journal::UNDO_RUN_KIND));
+        assert_eq!(runs[0].target_session_id.as_deref(), Some(first.session_id.as_str()));
+        assert_eq!(runs[1].session_id, second.session_id);
+        assert_eq!(runs[1].undo_state, journal::UndoState::NotUndone);
+        assert_eq!(runs[2].undo_state, journal::UndoState::Undone);
+        assert_eq!(runs[2].undone, 1);
+
+        let again = journal::undo_run(&journal_path, &first.session_id, root.as_path(), mode)
+            .expect("undo older run again");
+        assert_eq!(again.restored, 0);
+        assert_eq!(again.skipped, 1);
+        assert_eq!(again.details[0].message, "already undone");
+
+        assert!(journal::undo_run(&journal_path, "missing", root.as_path(), mode).is_err());
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_selective_undo_restores_only_chosen_moves() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+        let journal_path = root.with_extension("journal.jsonl");
+
+        write_file(&root.join("Load/invoice.txt"), b"doc");
+        write_file(&root.join("Load/song.mp3"), b"audio");
+        write_file(&root.join("Load/notes.md"), b"notes");
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.moved, 3);
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+
+        let destination_of = |name: &str| {
+            run.moved_files
+                .iter()
+                .find(|moved| moved.source_path.ends_with(name))
+                .map(|moved| PathBuf::from(&moved.destination_path))
+                .expect("moved file")
+        };
+        let invoice_source = root.join("Load/invoice.txt").to_string_lossy().to_string();
+        let selection = journal::UndoSelection {
+            paths: vec![invoice_source, root.join("Load/other.txt").to_string_lossy().to_string()],
+            patterns: vec!["*.mp3".to_string()],
+        };
+
+        let undo = journal::undo_selected(
+            &journal_path,
+            &run.session_id,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+            &selection,
+        )
+        .expect("selective undo");
+        assert_eq!(undo.restored, 2);
+        assert_eq!(undo.skipped, 1);
+        assert!(undo
+            .details
+            .iter()
+            .any(|detail| detail.status == "skipped" && detail.message == "not part of this run"));
+        assert!(!destination_of("invoice.txt").exists());
+        assert!(!destination_of("song.mp3").exists());
+        assert!(destination_of("notes.md").exists());
+
+        let runs = journal::list_runs(&journal_path).expect("list runs");
+        assert_eq!(runs[1].session_id, run.session_id);
+        assert_eq!(runs[1].undo_state, journal::UndoState::PartiallyUndone);
+        assert_eq!((runs[1].undone, runs[1].undoable), (2, 1));
+
+        let empty = journal::UndoSelection::default();
+        let mode = journal::UndoMode::RestoredFolder;
+        assert!(
+            journal::undo_selected(&journal_path, &run.session_id, root.as_path(), mode, &empty)
+                .is_err()
+        );
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_undo_to_original_location() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+        let journal_path = root.with_extension("journal.jsonl");
+
+        write_file(&root.join("Load/invoice.txt"), b"doc");
+        write_file(&root.join("Load/song.mp3"), b"audio");
+        write_file(&root.join("Load/gone/notes.md"), b"notes");
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.moved, 3);
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+
+        // A new file took the song's old name and the notes' folder was deleted.
+        write_file(&root.join("Load/song.mp3"), b"newer audio");
+        fs::remove_dir_all(root.join("Load/gone")).expect("remove folder");
+
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::OriginalLocation,
+        )
+        .expect("undo to original location");
+        assert_eq!(undo.restored, 3);
+        assert_eq!(undo.conflicts, 1);
+        assert_eq!(fs::read(root.join("Load/invoice.txt")).expect("invoice"), b"doc");
+        assert_eq!(fs::read(root.join("Load/song.mp3")).expect("song"), b"newer audio");
+        let conflict = undo
+            .details
+            .iter()
+            .find(|detail| detail.status == "conflict")
+            .expect("conflict detail");
+        assert!(conflict.source_path.ends_with("song.mp3"));
+        let conflict_path = conflict
+            .message
+            .strip_prefix("restored to conflict path ")
+            .map(PathBuf::from)
+            .expect("conflict path");
+        assert_eq!(conflict_path.parent(), Some(root.join("Load").as_path()));
+        assert_eq!(fs::read(&conflict_path).expect("restored song"), b"audio");
+        assert!(!root.join("Load/gone").exists());
+        assert!(walkdir::WalkDir::new(root.join("Restored").join(&run.session_id))
+            .into_iter()
+            .filter_map(Result::ok)
+            .any(|entry| entry.path().ends_with("Load/gone/notes.md")));
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_redo_reapplies_an_undo() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+        let journal_path = root.with_extension("journal.jsonl");
+
+        write_file(&root.join("Load/invoice.txt"), b"doc");
+        write_file(&root.join("Load/song.mp3"), b"audio");
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+        let destination_of = |name: &str| {
+            run.moved_files
+                .iter()
+                .find(|moved| moved.source_path.ends_with(name))
+                .map(|moved| PathBuf::from(&moved.destination_path))
+                .expect("moved file")
+        };
+
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo");
+        assert_eq!(undo.restored, 2);
+        let undo_session_id = undo.undo_session_id.expect("undo entry");
+
+        // The undo entry is not undoable itself, and the last run is still the sort.
+        assert!(journal::undo_run(
+            &journal_path,
+            &undo_session_id,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .is_err());
+        let last = journal::load_last_run(&journal_path).expect("load").expect("run");
+        assert_eq!(last.session_id, run.session_id);
+
+        // Something new took the song's place in the meantime.
+        write_file(&destination_of("song.mp3"), b"other audio");
+
+        let undo_entry = journal::load_redo_source(&journal_path, Some(&run.session_id))
+            .expect("redo source");
+        assert_eq!(undo_entry.session_id, undo_session_id);
+        let candidates: Vec<_> = journal::redo_candidates(&undo_entry).collect();
+
+        // A policy that would discard the restored song renames it instead.
+        let mut keep_newer = rules.clone();
+        keep_newer.global.collision_policy = rules::CollisionPolicy::KeepNewer;
+        let guarded = planner::build_redo_plan(&keep_newer, &candidates);
+        assert_eq!(guarded.move_count, 2);
+        assert!(guarded.moves.iter().all(|entry| {
+            entry.collision.as_ref().map(|decision| decision.action)
+                != Some(planner::CollisionAction::TrashedIncoming)
+        }));
+
+        let plan = planner::build_redo_plan(&rules, &candidates);
+        assert_eq!(plan.move_count, 2);
+        assert_eq!(plan.potential_conflicts, 1);
+        let redo = executor::execute_plan_silent(&plan).expect("execute redo");
+        assert_eq!(redo.errors, 0);
+
+        // The app stops before the redo is journaled; recovery still marks the undo redone.
+        let mut wal =
+            journal::WriteAhead::begin_redo(&journal_path, &redo.session_id, &undo_session_id)
+                .expect("begin redo log");
+        for moved in &redo.moved_files {
+            wal.intent(moved).expect("log intent");
+            wal.done(&moved.source_path).expect("log done");
+        }
+        drop(wal);
+        let recovered = journal::recover_unfinished_runs(&journal_path).expect("recover redo");
+        assert_eq!(recovered[0].journaled, 2);
+
+        assert_eq!(fs::read(destination_of("invoice.txt")).expect("invoice"), b"doc");
+        assert_eq!(fs::read(destination_of("song.mp3")).expect("song"), b"other audio");
+        let renamed = redo
+            .moved_files
+            .iter()
+            .find(|moved| moved.collision_renamed)
+            .expect("renamed song");
+        assert_eq!(fs::read(&renamed.destination_path).expect("renamed song"), b"audio");
+
+        let runs = journal::list_runs(&journal_path).expect("list runs");
+        assert_eq!(runs[0].kind.as_deref(), Some(journal::REDO_RUN_KIND));
+        assert_eq!(runs[0].target_session_id.as_deref(), Some(undo_session_id.as_str()));
+        assert_eq!(runs[0].undoable, 2);
+        assert_eq!(runs[1].session_id, undo_session_id);
+        assert_eq!(runs[1].redoable, 0);
+        assert!(journal::load_redo_source(&journal_path, None).is_err());
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_profiles_keep_separate_rules_and_roots() {
+        let base = temp_sort_root();
+        let photos_root = base.join("photos");
+        let docs_root = base.join("docs");
+
+        let mut photos_rules = make_rules(&photos_root);
+        photos_rules.global.require_stable_size = false;
+        let mut docs_rules = make_rules(&docs_root);
+        docs_rules.global.require_stable_size = false;
+        docs_rules.categories.retain(|category| category.id == "documents");
+        docs_rules.bundles.clear();
+
+        let photos = Profile::new(
+            "photos".to_string(),
+            photos_rules.clone(),
+            base.join("config/photos/rules.json"),
+            base.join("config/photos/journal.jsonl"),
+        );
+        let docs = Profile::new(
+            "docs".to_string(),
+            docs_rules.clone(),
+            base.join("config/docs/rules.json"),
+            base.join("config/docs/journal.jsonl"),
+        );
+        let state = AppState::new(vec![photos.clone(), docs.clone()]);
+
+        write_file(&photos_root.join("Inbox/cat.png"), b"png");
+        write_file(&docs_root.join("Inbox/cat.png"), b"png");
+        write_file(&docs_root.join("Inbox/notes.txt"), b"doc");
+
+        let photos_plan = dry_run_internal(&photos, RunMode::Sort).expect("photos plan");
+        assert_eq!(photos_plan.move_count, 1);
+        let docs_plan = dry_run_internal(&docs, RunMode::Sort).expect("docs plan");
+        let docs_targets: BTreeSet<String> = docs_plan
+            .moves
+            .iter()
+            .map(|item| item.category.clone())
+            .collect();
+        assert_eq!(docs_targets, BTreeSet::from(["Documents".to_string(), "Misc".to_string()]));
+
+        assert!(ensure_distinct_sort_root(
+            &state,
+            &docs,
+            &photos_root.join("nested").to_string_lossy()
+        )
+        .is_err());
+        assert!(ensure_distinct_sort_root(&state, &docs, &base.to_string_lossy()).is_err());
+        assert!(ensure_distinct_sort_root(&state, &docs, &docs_root.to_string_lossy()).is_ok());
+
+        // Inbox folders count too: docs may not drain a folder inside the photos root.
+        let mut draining = docs_rules.clone();
+        draining.global.inbox_folders = vec![rules::InboxFolder {
+            path: photos_root.join("Inbox").to_string_lossy().to_string(),
+            recursive: false,
+        }];
+        assert!(ensure_distinct_roots(&state.profiles.lock().expect("profiles"), "docs", &draining)
+            .is_err());
+
+        let mut updated = docs.current_rules().expect("docs rules");
+        updated.global.min_file_age_seconds = 42;
+        set_rules_internal(&docs, updated).expect("save docs rules");
+        assert_eq!(
+            state
+                .profile(Some("photos"))
+                .and_then(|profile| profile.current_rules())
+                .expect("photos rules")
+                .global
+                .min_file_age_seconds,
+            0
+        );
+        assert!(base.join("config/docs/rules.json").exists());
+        assert!(!base.join("config/photos/rules.json").exists());
+        assert!(state.profile(Some("missing")).is_err());
+
+        assert!(rules::validate_profile_id("work-2").is_ok());
+        assert!(rules::validate_profile_id(rules::DEFAULT_PROFILE_ID).is_err());
+        assert!(rules::validate_profile_id("../etc").is_err());
+
+        tear_down(&base);
+    }
+
+    #[test]
+    fn acceptance_inbox_folders_feed_the_sort_root() {
+        let base = temp_sort_root();
+        let root = base.join("sort");
+        let downloads = base.join("Downloads");
+        let scans = base.join("Scans");
+
+        let mut rules = make_rules(&root);
+        rules.global.inbox_folders = vec![
+            rules::InboxFolder {
+                path: downloads.to_string_lossy().to_string(),
+                recursive: false,
+            },
+            rules::InboxFolder {
+                path: scans.to_string_lossy().to_string(),
+                recursive: true,
+            },
+        ];
+        assert!(rules::validate_rules(&rules).valid);
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&downloads.join("song.mp3"), b"audio");
+        write_file(&downloads.join("Albums/track.mp3"), b"audio");
+        write_file(&downloads.join("myrepo/.git/HEAD"), b"ref: refs/heads/main");
+        write_file(&scans.join("2024/receipt.pdf"), b"pdf");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        let moved: BTreeSet<_> = plan
+            .moves
+            .iter()
+            .map(|entry| PathBuf::from(&entry.source_path))
+            .collect();
+        assert_eq!(
+            moved,
+            BTreeSet::from([
+                downloads.join("song.mp3"),
+                downloads.join("myrepo"),
+                scans.join("2024/receipt.pdf"),
+            ])
+        );
+
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert!(root.join("Audio/song.mp3").exists());
+        assert!(root.join("Code/myrepo/.git/HEAD").exists());
+        assert!(root.join("Documents/receipt.pdf").exists());
+        assert!(downloads.join("Albums/track.mp3").exists());
+
+        let journal_path = base.join("journal.jsonl");
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+        let last = journal::load_last_run(&journal_path)
+            .expect("load journal")
+            .expect("journal run");
+        let originals: BTreeSet<_> = last
+            .moves
+            .iter()
+            .map(|movement| PathBuf::from(&movement.original_path))
+            .collect();
+        assert_eq!(originals, moved);
+
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo last run");
+        assert_eq!(undo.errors, 0);
+        assert_eq!(undo.restored, 3);
+        assert!(!root.join("Audio/song.mp3").exists());
+
+        rules.global.inbox_folders.push(rules::InboxFolder {
+            path: root.join("Nested").to_string_lossy().to_string(),
+            recursive: false,
+        });
+        assert!(!rules::validate_rules(&rules).valid);
+
+        tear_down(&base);
+    }
+
+    #[test]
+    fn acceptance_rules_saves_are_atomic_and_versioned() {
+        let base = temp_sort_root();
+        let rules_path = base.join("config/rules.json");
+        let mut rules = make_rules(&base.join("sort"));
+
+        for age in 0..(rules::RULES_HISTORY_LIMIT as u64 + 3) {
+            rules.global.min_file_age_seconds = age;
+            rules::save_rules(&rules_path, &rules).expect("save rules");
+        }
+        // Saving identical contents does not add a version.
+        rules::save_rules(&rules_path, &rules).expect("save unchanged rules");
+
+        assert!(!rules_path.with_extension("json.tmp").exists());
+        let loaded = rules::load_or_create_rules(&rules_path).expect("load rules");
+        assert_eq!(loaded.global.min_file_age_seconds, rules::RULES_HISTORY_LIMIT as u64 + 2);
+
+        let versions = rules::list_rules_versions(&rules_path).expect("list versions");
+        assert_eq!(versions.len(), rules::RULES_HISTORY_LIMIT);
+        let newest = rules::load_rules_version(&rules_path, &versions[0].id).expect("newest");
+        assert_eq!(newest.global.min_file_age_seconds, rules::RULES_HISTORY_LIMIT as u64 + 1);
+        let oldest = rules::load_rules_version(&rules_path, &versions[versions.len() - 1].id)
+            .expect("oldest");
+        assert_eq!(oldest.global.min_file_age_seconds, 2);
+        assert_eq!(
+            versions[0].sort_root.as_deref(),
+            Some(rules.global.sort_root.as_str())
+        );
+
+        let profile = Profile::new(
+            rules::DEFAULT_PROFILE_ID.to_string(),
+            loaded,
+            rules_path.clone(),
+            base.join("config/journal.jsonl"),
+        );
+        set_rules_internal(&profile, oldest).expect("restore oldest");
+        assert_eq!(
+            rules::load_or_create_rules(&rules_path)
+                .expect("reload")
+                .global
+                .min_file_age_seconds,
+            2
+        );
+
+        assert!(rules::load_rules_version(&rules_path, "../rules.json").is_err());
+
+        tear_down(&base);
+    }
+
+    #[test]
+    fn acceptance_explain_classification_reports_matches_and_shadowing() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.min_file_age_seconds = 3600;
+        let documents = rules
+            .categories
+            .iter_mut()
+            .find(|category| category.id == "documents")
+            .expect("documents category");
+        documents.filename_globs = vec!["invoice-*".to_string()];
+        let video = rules
+            .categories
+            .iter_mut()
+            .find(|category| category.id == "video")
+            .expect("video category");
+        video.extensions.push("PNG".to_string());
+
+        write_file(&root.join("Drop/invoice-7.png"), b"img");
+        write_file(&root.join("Drop/photo.png"), b"img");
+        write_file(&root.join("Drop/data.weird"), b"???");
+
+        let invoice = planner::explain_classification(&rules, &root.join("Drop/invoice-7.png"))
+            .expect("explain invoice");
+        let matched = invoice.matched.expect("invoice match");
+        assert_eq!(matched.kind, "filenameGlob");
+        assert_eq!(matched.category_id.as_deref(), Some("documents"));
+        let next = invoice.next.expect("invoice next");
+        assert_eq!((next.kind.as_str(), next.category_id.as_deref()), ("extension", Some("images")));
+        assert!(!invoice.old_enough);
+        assert!(invoice.case_insensitive_ext);
+
+        let photo = planner::explain_classification(&rules, &root.join("Drop/photo.png"))
+            .expect("explain photo");
+        assert_eq!(photo.extension, "png");
+        assert_eq!(photo.matched.expect("photo match").category_id.as_deref(), Some("images"));
+        assert_eq!(photo.next.expect("photo next").category_id.as_deref(), Some("video"));
+        assert_eq!(photo.shadowed_extensions.len(), 1);
+        assert_eq!(photo.shadowed_extensions[0].winner, "Images");
+        assert_eq!(photo.shadowed_extensions[0].shadowed, vec!["Video".to_string()]);
+
+        let weird = planner::explain_classification(&rules, &root.join("Drop/data.weird"))
+            .expect("explain unknown");
+        assert_eq!(weird.matched.expect("misc match").kind, "misc");
+        assert!(weird.next.is_none());
+
+        rules.global.unknown_goes_to_misc = false;
+        let skipped = planner::explain_classification(&rules, &root.join("Drop/data.weird"))
+            .expect("explain skipped");
+        assert!(skipped.matched.is_none());
+        assert!(skipped.skip_reason.is_some());
+
+        assert!(planner::explain_classification(&rules, &root.join("missing.txt")).is_err());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_rule_impact_preview_reports_reclassified_library_files() {
+        let root = temp_sort_root();
+        let current = make_rules(&root);
+        rules::ensure_sort_root_dirs(&current).expect("ensure sort root dirs");
+
+        write_file(&root.join("Images/a.png"), b"img");
+        write_file(&root.join("Images/b.jpg"), b"img");
+        write_file(&root.join("Documents/c.txt"), b"doc");
+        write_file(&root.join("Audio/song.mp3"), b"audio");
+        write_file(&root.join("Drop/new.png"), b"img");
+        write_file(&root.join("Documents/a.png"), b"taken");
+
+        let mut candidate = current.clone();
+        // Neither the run gates nor the collision policy change what the rules decide.
+        candidate.global.collision_policy = rules::CollisionPolicy::Skip;
+        candidate.global.min_file_age_seconds = 3600;
+        candidate.global.require_stable_size = true;
+        for category in candidate.categories.iter_mut() {
+            match category.id.as_str() {
+                "images" => category.extensions.retain(|ext| ext != "png"),
+                "documents" => category.extensions.push("png".to_string()),
+                "audio" => category.target_subfolder = "Music".to_string(),
+                _ => {}
+            }
+        }
+        // A sort root edited alongside the categories does not move the library being previewed.
+        candidate.global.sort_root = root.join("Elsewhere").to_string_lossy().to_string();
+
+        let impact = planner::rule_impact(&current, &candidate).expect("rule impact");
+        assert_eq!(impact.files_examined, 5);
+        assert!(impact.skips.is_empty());
+        let moved: BTreeSet<(String, String)> = impact
+            .reclassified
+            .iter()
+            .map(|file| (file.from_folder.clone(), file.to_folder.clone()))
+            .collect();
+        assert_eq!(
+            moved,
+            BTreeSet::from([
+                ("Audio".to_string(), "Music".to_string()),
+                ("Images".to_string(), "Documents".to_string()),
+            ])
+        );
+        let png = impact
+            .reclassified
+            .iter()
+            .find(|file| file.path.ends_with("a.png"))
+            .expect("a.png reclassified");
+        assert_eq!(Path::new(&png.target_folder), root.join("Documents"));
+        let counts: BTreeSet<(String, u64, u64)> = impact
+            .categories
+            .iter()
+            .map(|impact| (impact.folder.clone(), impact.gained, impact.lost))
+            .collect();
+        assert_eq!(
+            counts,
+            BTreeSet::from([
+                ("Audio".to_string(), 0, 1),
+                ("Documents".to_string(), 1, 0),
+                ("Images".to_string(), 0, 1),
+                ("Music".to_string(), 1, 0),
+            ])
+        );
+
+        assert!(root.join("Images/a.png").exists());
+        assert!(root.join("Audio/song.mp3").exists());
+        assert!(!root.join("Music").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_library_resort_moves_stranded_files_and_undoes() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.cleanup_empty_folders.enabled = false;
+        rules::ensure_sort_root_dirs(&rules).expect("ensure sort root dirs");
+
+        write_file(&root.join("Images/scan.png"), b"img");
+        write_file(&root.join("Images/photo.jpg"), b"img");
+        write_file(&root.join("Code/myrepo/.git/HEAD"), b"ref: refs/heads/main");
+        write_file(&root.join("Misc/notes.txt"), b"doc");
+
+        for category in rules.categories.iter_mut() {
+            match category.id.as_str() {
+                "images" => category.extensions.retain(|ext| ext != "png"),
+                "documents" => category.extensions.push("png".to_string()),
+                _ => {}
+            }
+        }
+        let library = rules::protected_top_level_folders(&rules);
+
+        // The run gates apply too: files written moments ago are held back.
+        let mut gated = rules.clone();
+        gated.global.require_stable_size = true;
+        let held = planner::build_library_plan(&gated, &library).expect("gated library plan");
+        assert_eq!(held.move_count, 0);
+        for stranded in ["Images/scan.png", "Misc/notes.txt"] {
+            assert!(held
+                .skips
+                .iter()
+                .any(|skip| Path::new(&skip.path) == root.join(stranded)
+                    && skip.reason.contains("stabilise")));
+        }
+
+        let plan = planner::build_library_plan(&rules, &library).expect("library plan");
+        let moved: BTreeSet<_> = plan
+            .moves
+            .iter()
+            .map(|entry| PathBuf::from(&entry.destination_path))
+            .collect();
+        assert_eq!(
+            moved,
+            BTreeSet::from([root.join("Documents/scan.png"), root.join("Documents/notes.txt")])
+        );
+
+        let run = executor::execute_plan_silent(&plan).expect("execute library plan");
+        assert_eq!(run.errors, 0);
+        assert!(root.join("Documents/scan.png").exists());
+        assert!(root.join("Images/photo.jpg").exists());
+        assert!(root.join("Code/myrepo/.git/HEAD").exists());
+        assert_eq!(
+            planner::build_library_plan(&rules, &library)
+                .expect("second library plan")
+                .move_count,
+            0
+        );
+
+        let journal_path = root.join("journal.jsonl");
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+        let undo = journal::undo_last_run(
+            &journal_path,
+            root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo last run");
+        assert_eq!(undo.restored, 2);
+        assert!(!root.join("Documents/scan.png").exists());
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_relocation_moves_category_contents_and_undo_restores_in_place() {
+        let root = temp_sort_root();
+        let new_root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        // Relocation renames collisions whatever the policy says.
+        rules.global.collision_policy = rules::CollisionPolicy::OverwriteToTrash;
+        write_file(&root.join("Images/photo.jpg"), b"img");
+        write_file(&root.join("Documents/2024/report.pdf"), b"pdf");
+        write_file(&new_root.join("Images/photo.jpg"), b"already there");
+        write_file(&root.join("loose.txt"), b"not yet sorted");
+
+        assert!(relocation_plan(&rules, root.join("nested").to_str().unwrap()).is_err());
+        assert!(relocation_plan(&rules, "relative/path").is_err());
+
+        let plan = relocation_plan(&rules, new_root.to_str().unwrap()).expect("relocation plan");
+        assert_eq!(plan.move_count, 2);
+        assert_eq!(plan.potential_conflicts, 1);
+
+        let run = executor::execute_plan_silent(&plan).expect("execute relocation");
+        assert_eq!(run.errors, 0);
+        assert!(new_root.join("Images/photo (1).jpg").exists());
+        assert!(new_root.join("Documents/2024/report.pdf").exists());
+        assert!(root.join("loose.txt").exists());
+
+        let journal_path = root.join("journal.jsonl");
+        journal::append_relocation(
+            &journal_path,
+            &run.session_id,
+            &run.moved_files,
+            &rules.global.sort_root,
+        )
+        .expect("append relocation");
+        let last = journal::load_last_run(&journal_path)
+            .expect("load journal")
+            .expect("relocation run");
+        assert_eq!(last.kind.as_deref(), Some(journal::RELOCATION_RUN_KIND));
+        assert_eq!(last.previous_sort_root.as_deref(), Some(rules.global.sort_root.as_str()));
+
+        let undo = journal::undo_last_run(
+            &journal_path,
+            new_root.as_path(),
+            journal::UndoMode::RestoredFolder,
+        )
+        .expect("undo");
+        assert_eq!(undo.restored, 2);
+        assert!(root.join("Images/photo.jpg").exists());
+        assert!(root.join("Documents/2024/report.pdf").exists());
+        assert!(!new_root.join("Restored").exists());
+        assert_eq!(
+            fs::read(new_root.join("Images/photo.jpg")).expect("read untouched file"),
+            b"already there"
+        );
+
+        tear_down(&root);
+        tear_down(&new_root);
+    }
+
+    #[test]
+    fn acceptance_collision_policies_are_applied_and_journaled() {
+        use planner::CollisionAction;
+
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        write_file(&root.join("Documents/same.txt"), b"identical");
+        write_file(&root.join("Documents/other.txt"), b"old");
+        write_file(&root.join("Drop/same.txt"), b"identical");
+        write_file(&root.join("Drop/other.txt"), b"different");
+
+        rules.global.collision_policy = rules::CollisionPolicy::Skip;
+        let plan = planner::build_plan(&rules).expect("skip plan");
+        assert_eq!(plan.move_count, 0);
+        assert_eq!(plan.potential_conflicts, 2);
+        assert!(plan.skips.iter().all(|skip| skip.reason.contains("collision policy: skip")));
+
+        rules.global.collision_policy = rules::CollisionPolicy::DedupeIfIdentical;
+        let plan = planner::build_plan(&rules).expect("dedupe plan");
+        let action_for = |name: &str| {
+            plan.moves
+                .iter()
+                .find(|entry| entry.source_path.ends_with(name))
+                .and_then(|entry| entry.collision.as_ref())
+                .map(|decision| decision.action)
+        };
+        assert_eq!(action_for("same.txt"), Some(CollisionAction::TrashedIncoming));
+        assert_eq!(action_for("other.txt"), Some(CollisionAction::Renamed));
+
+        let run = executor::execute_plan_silent(&plan).expect("execute dedupe plan");
+        assert_eq!(run.errors, 0);
+        assert!(!root.join("Drop/same.txt").exists());
+        assert_eq!(fs::read(root.join("Documents/other (1).txt")).expect("read"), b"different");
+
+        // Outside the sort root so the next plan does not pick it up.
+        let journal_path = root.with_extension("journal.jsonl");
+        journal::append_run(&journal_path, &run.session_id, &run.moved_files, &HashMap::new())
+            .expect("append run");
+        let last = journal::load_last_run(&journal_path).expect("load").expect("run");
+        let trashed = last
+            .moves
+            .iter()
+            .find(|movement| movement.original_path.ends_with("same.txt"))
+            .expect("journaled dedupe");
+        assert_eq!(trashed.status, journal::TRASHED_STATUS);
+
+        write_file(&root.join("Drop/other.txt"), b"the largest version");
+        rules.global.collision_policy = rules::CollisionPolicy::KeepLarger;
+        let plan = planner::build_plan(&rules).expect("keep larger plan");
+        assert_eq!(plan.moves.len(), 1);
+        assert_eq!(
+            plan.moves[0].collision.as_ref().map(|decision| decision.action),
+            Some(CollisionAction::ReplacedExisting)
+        );
+        executor::execute_plan_silent(&plan).expect("execute keep larger plan");
+        assert_eq!(
+            fs::read(root.join("Documents/other.txt")).expect("read"),
+            b"the largest version"
+        );
+
+        // The kept file is only trashed once the incoming one is staged beside it.
+        write_file(&root.join("Drop/other.txt"), b"an even larger version");
+        let plan = planner::build_plan(&rules).expect("blocked replace plan");
+        let staged = executor::replace_staging_path(&root.join("Documents/other.txt"));
+        write_file(&staged, b"left over");
+        let run = executor::execute_plan_silent(&plan).expect("execute blocked replace");
+        assert_eq!(run.errors, 1);
+        assert!(run.moved_files.is_empty());
+        assert_eq!(
+            fs::read(root.join("Documents/other.txt")).expect("read"),
+            b"the largest version"
+        );
+        assert!(root.join("Drop/other.txt").exists());
+        fs::remove_file(&staged).expect("remove staged file");
+        fs::remove_file(root.join("Drop/other.txt")).expect("remove incoming file");
+
+        // A file never replaces a directory that happens to have its name.
+        fs::create_dir_all(root.join("Documents/notes.txt")).expect("create directory");
+        write_file(&root.join("Drop/notes.txt"), b"notes");
+        rules.global.collision_policy = rules::CollisionPolicy::OverwriteToTrash;
+        let plan = planner::build_plan(&rules).expect("overwrite plan");
+        assert_eq!(plan.moves.len(), 1);
+        assert_eq!(
+            plan.moves[0].collision.as_ref().map(|decision| decision.action),
+            Some(CollisionAction::Renamed)
+        );
+        assert!(plan.moves[0].destination_path.ends_with("notes (1).txt"));
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_duplicate_detection_marks_and_moves_identical_files() {
+        let root = temp_sort_root();
+        let mut rules = make_rules(&root);
+        rules.global.duplicate_detection.enabled = true;
+        rules.global.duplicate_detection.action = rules::DuplicateAction::MoveToDuplicates;
+        write_file(&root.join("Documents/invoice.pdf"), b"invoice");
+        write_file(&root.join("Drop/invoice.pdf"), b"invoice");
+        write_file(&root.join("Drop/report.pdf"), b"report");
+        write_file(&root.join("Drop/report (1).pdf"), b"report");
+        write_file(&root.join("Drop/notes.pdf"), b"report!");
+
+        let plan = planner::build_plan(&rules).expect("build plan");
+        assert_eq!(plan.duplicate_count, 2);
+        let duplicates: BTreeSet<_> = plan
+            .moves
+            .iter()
+            .filter_map(|entry| entry.duplicate.as_ref().map(|mark| mark.original.clone()))
+            .collect();
+        let existing_invoice = root.join("Documents/invoice.pdf");
+        assert!(duplicates.contains(existing_invoice.to_string_lossy().as_ref()));
+
+        let run = executor::execute_plan_silent(&plan).expect("execute plan");
+        assert_eq!(run.errors, 0);
+        assert!(root.join("Duplicates/invoice.pdf").exists());
+        assert!(root.join("Documents/notes.pdf").exists());
+        let report_copies = ["Documents/report.pdf", "Documents/report (1).pdf"]
+            .iter()
+            .filter(|name| root.join(name).exists())
+            .count();
+        assert_eq!(report_copies, 1);
+        assert_eq!(planner::build_plan(&rules).expect("second plan").move_count, 0);
+
+        rules.global.duplicate_detection.action = rules::DuplicateAction::Trash;
+        write_file(&root.join("Drop/notes.pdf"), b"report!");
+        let plan = planner::build_plan(&rules).expect("trash plan");
+        assert_eq!(plan.duplicate_count, 1);
+        assert_eq!(plan.moves[0].destination_path, "");
+        executor::execute_plan_silent(&plan).expect("execute trash plan");
+        assert!(!root.join("Drop/notes.pdf").exists());
+        assert!(!root.join("Documents/notes (1).pdf").exists());
+
+        // A copy its collision policy trashes is no original: the identical copy still moves.
+        rules.global.collision_policy = rules::CollisionPolicy::KeepNewer;
+        write_file(&root.join("Documents/draft.txt"), b"kept version");
+        write_file(&root.join("Drop/draft.txt"), b"older draft");
+        write_file(&root.join("Drop/draft copy.txt"), b"older draft");
+        let earlier = std::time::SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(root.join("Drop/draft.txt"))
+            .and_then(|file| file.set_modified(earlier))
+            .expect("age draft.txt");
+        let plan = planner::build_plan(&rules).expect("keep newer plan");
+        let copy = plan
+            .moves
+            .iter()
+            .find(|entry| entry.source_path.ends_with("draft copy.txt"))
+            .expect("copy planned");
+        assert!(copy.duplicate.is_none());
+        assert_eq!(PathBuf::from(&copy.destination_path), root.join("Documents/draft copy.txt"));
+
+        tear_down(&root);
+    }
+
+    #[test]
+    fn acceptance_library_duplicate_report_and_trash() {
+        let root = temp_sort_root();
+        let rules = make_rules(&root);
+        write_file(&root.join("Images/photo.jpg"), b"same picture");
+        write_file(&root.join("Images/2023/photo copy.jpg"), b"same picture");
+        write_file(&root.join("Misc/photo.bin"), b"same picture");
+        write_file(&root.join("Documents/a.txt"), b"same length!");
+        write_file(&root.join("Documents/empty-1.txt"), b"");
+        write_file(&root.join("Documents/empty-2.txt"), b"");
+        write_file(&root.join("Code/repo/.git/HEAD"), b"same picture");
+        write_file(&root.join("Drop/photo.jpg"), b"same picture");
+
+        let report = duplicates::find_library_duplicates(&rules).expect("duplicate report");
+        assert_eq!(report.sets.len(), 1);
+        let set = &report.sets[0];
+        assert_eq!(set.files.len(), 3);
+        assert_eq!(set.size, 12);
+        assert_eq!(set.reclaimable_bytes, 24);
+        assert_eq!(report.reclaimable_bytes, 24);
+
+        let journal_path = root.with_extension("journal.jsonl");
+        let selected = vec![
+            root.join("Images/2023/photo copy.jpg").to_string_lossy().to_string(),
+            root.join("Documents/a.txt").to_string_lossy().to_string(),
+        ];
+        let result = duplicates::trash_duplicates(&rules, &journal_path, &selected)
+            .expect("trash duplicates");
+        assert_eq!(result.trashed, 1);
+        assert_eq!(result.skipped, 1);
+        assert_eq!(result.reclaimed_bytes, 12);
+        assert!(!root.join("Images/2023/photo copy.jpg").exists());
+        assert!(root.join("Documents/a.txt").exists());
+
+        let trash_run = journal::load_run(&journal_path, &result.session_id)
+            .expect("load")
+            .expect("duplicate trash run");
+        assert_eq!(trash_run.kind.as_deref(), Some(journal::DUPLICATE_TRASH_RUN_KIND));
+        assert_eq!(trash_run.moves.len(), 1);
+        assert_eq!(trash_run.moves[0].status, journal::TRASHED_STATUS);
+        assert_eq!(trash_run.moves[0].new_path, "");
+        // Nothing in it can be undone, so "undo last run" looks past it.
+        assert!(journal::load_last_run(&journal_path).expect("load").is_none());
+
+        let remaining = duplicates::find_library_duplicates(&rules).expect("second report");
+        assert_eq!(remaining.sets[0].files.len(), 2);
+        let everything = remaining.sets[0].files.clone();
+        let result = duplicates::trash_duplicates(&rules, &journal_path, &everything)
+            .expect("trash every copy");
+        assert_eq!(result.trashed, 1);
+        assert!(root.join("Images/photo.jpg").exists());
+
+        let _ = fs::remove_file(&journal_path);
+        tear_down(&root);
+    }
 } // end mod acceptance_tests
@@ -0,0 +1,188 @@
+use crate::errors::{AppError, AppResult};
+use crate::rules::default_in_progress_patterns;
+use serde_json::{json, Map, Value};
+
+/// Schema version written by this build. Bump it and append a step to `MIGRATIONS`
+/// whenever the on-disk shape of `rules.json` changes.
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+/// Files written before `schemaVersion` existed.
+const UNVERSIONED_SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades version `i + 1` to `i + 2`.
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Reads `schemaVersion` from a raw `rules.json` document; files without one are version 1.
+pub fn schema_version(document: &Value) -> AppResult<u32> {
+    let Some(raw) = document.get("schemaVersion") else {
+        return Ok(UNVERSIONED_SCHEMA_VERSION);
+    };
+
+    raw.as_u64()
+        .and_then(|version| u32::try_from(version).ok())
+        .filter(|version| *version >= UNVERSIONED_SCHEMA_VERSION)
+        .ok_or_else(|| AppError::Validation(format!("invalid schemaVersion {}", raw)))
+}
+
+/// Upgrades `document` in place to `CURRENT_SCHEMA_VERSION` and returns the version it
+/// started at. Versions newer than this build are refused rather than guessed at.
+pub fn migrate(document: &mut Value) -> AppResult<u32> {
+    let found = schema_version(document)?;
+    if found > CURRENT_SCHEMA_VERSION {
+        return Err(AppError::UnsupportedSchemaVersion {
+            found,
+            supported: CURRENT_SCHEMA_VERSION,
+        });
+    }
+
+    let Some(root) = document.as_object_mut() else {
+        return Err(AppError::Validation("rules.json must be a JSON object".to_string()));
+    };
+
+    for version in found..CURRENT_SCHEMA_VERSION {
+        let step = MIGRATIONS[(version - UNVERSIONED_SCHEMA_VERSION) as usize];
+        step(root).map_err(|err| {
+            AppError::Validation(format!(
+                "migrating rules.json from schema {} to {} failed: {}",
+                version,
+                version + 1,
+                err
+            ))
+        })?;
+        root.insert("schemaVersion".to_string(), json!(version + 1));
+    }
+
+    Ok(found)
+}
+
+/// v2 writes out every setting added since the first release, using the values the app
+/// assumed while they were absent, so the file documents the behaviour it actually gets.
+fn migrate_v1_to_v2(root: &mut Map<String, Value>) -> Result<(), String> {
+    let global = root
+        .get_mut("global")
+        .and_then(Value::as_object_mut)
+        .ok_or("missing 'global' object")?;
+    insert_missing(global, "contentSniffing", json!("off"));
+    insert_missing(global, "sortMode", json!("flatten"));
+    insert_missing(global, "excludePatterns", json!([]));
+    insert_missing(global, "inProgressPatterns", json!(default_in_progress_patterns()));
+    insert_missing(global, "requireStableSize", json!(false));
+    insert_missing(global, "skipOpenFiles", json!(false));
+    insert_missing(global, "inboxFolders", json!([]));
+
+    let categories = root
+        .get_mut("categories")
+        .and_then(Value::as_array_mut)
+        .ok_or("missing 'categories' array")?;
+    for category in categories {
+        let category = category
+            .as_object_mut()
+            .ok_or("every category must be an object")?;
+        insert_missing(category, "filenameGlobs", json!([]));
+        insert_missing(category, "filenameRegexes", json!([]));
+    }
+
+    insert_missing(root, "bundles", json!([]));
+    Ok(())
+}
+
+/// v3 adds content-hash duplicate detection, off by default.
+fn migrate_v2_to_v3(root: &mut Map<String, Value>) -> Result<(), String> {
+    let global = root
+        .get_mut("global")
+        .and_then(Value::as_object_mut)
+        .ok_or("missing 'global' object")?;
+    insert_missing(
+        global,
+        "duplicateDetection",
+        json!({ "enabled": false, "action": "keep" }),
+    );
+    Ok(())
+}
+
+fn insert_missing(object: &mut Map<String, Value>, key: &str, value: Value) {
+    object.entry(key.to_string()).or_insert(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn v1_document() -> Value {
+        json!({
+            "global": {
+                "sortRoot": "/tmp/Sort",
+                "caseInsensitiveExt": true,
+                "collisionPolicy": "rename",
+                "unknownGoesToMisc": true,
+                "noExtensionGoesToMisc": true,
+                "minFileAgeSeconds": 10,
+                "cleanupEmptyFolders": { "enabled": true, "minAgeSeconds": 60, "mode": "trash" }
+            },
+            "categories": [
+                { "id": "images", "name": "Images", "targetSubfolder": "Images", "extensions": ["png"] }
+            ],
+            "misc": { "name": "Misc", "targetSubfolder": "Misc" }
+        })
+    }
+
+    #[test]
+    fn unversioned_rules_are_migrated_and_backed_up() {
+        let dir = std::env::temp_dir().join(format!("rules-migrate-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("rules.json");
+        let original = serde_json::to_string_pretty(&v1_document()).expect("serialize v1");
+        fs::write(&path, &original).expect("write v1 rules");
+
+        let loaded = rules::load_or_create_rules(&path).expect("load migrated rules");
+        assert_eq!(loaded.schema_version, CURRENT_SCHEMA_VERSION);
+        assert!(!loaded.global.require_stable_size);
+
+        let rewritten: Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read rules")).expect("parse");
+        assert_eq!(rewritten["schemaVersion"], json!(CURRENT_SCHEMA_VERSION));
+        assert_eq!(rewritten["global"]["sortMode"], json!("flatten"));
+        assert_eq!(rewritten["global"]["duplicateDetection"]["enabled"], json!(false));
+
+        let backups: Vec<_> = fs::read_dir(&dir)
+            .expect("read dir")
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(backups[0].path()).expect("read backup"), original);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn current_files_without_gate_keys_keep_the_gates_on() {
+        let mut document = v1_document();
+        migrate(&mut document).expect("migrate v1");
+        let global = document["global"].as_object_mut().expect("global object");
+        global.remove("requireStableSize");
+        global.remove("skipOpenFiles");
+
+        let parsed: rules::Rules = serde_json::from_value(document).expect("parse rules");
+        assert!(parsed.global.require_stable_size);
+        assert!(parsed.global.skip_open_files);
+    }
+
+    #[test]
+    fn future_schema_versions_are_refused() {
+        let mut document = v1_document();
+        document["schemaVersion"] = json!(CURRENT_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            migrate(&mut document),
+            Err(AppError::UnsupportedSchemaVersion { .. })
+        ));
+
+        document["schemaVersion"] = json!("two");
+        assert!(migrate(&mut document).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Files under `root` that some process holds open for writing, mapped to `<pid>/<comm>`.
+///
+/// Linux only: walks `/proc/<pid>/fd` and reads the access mode from `/proc/<pid>/fdinfo`.
+/// Processes we are not allowed to inspect are silently ignored. Other platforms report nothing.
+/// Keys are canonical paths, as `/proc` reports them; look them up with canonical paths too.
+#[cfg(target_os = "linux")]
+pub fn open_for_write(root: &Path) -> HashMap<PathBuf, String> {
+    use std::fs;
+
+    let mut holders = HashMap::new();
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return holders;
+    };
+
+    for proc_entry in procs.flatten() {
+        let pid = proc_entry.file_name().to_string_lossy().to_string();
+        if pid.is_empty() || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+
+        let proc_dir = proc_entry.path();
+        let Ok(fds) = fs::read_dir(proc_dir.join("fd")) else {
+            continue;
+        };
+
+        let mut comm = None;
+        for fd_entry in fds.flatten() {
+            let Ok(target) = fs::read_link(fd_entry.path()) else {
+                continue;
+            };
+            if !target.starts_with(&root) {
+                continue;
+            }
+
+            let fdinfo = proc_dir.join("fdinfo").join(fd_entry.file_name());
+            if !is_write_mode(&fs::read_to_string(fdinfo).unwrap_or_default()) {
+                continue;
+            }
+
+            let comm = comm.get_or_insert_with(|| {
+                fs::read_to_string(proc_dir.join("comm"))
+                    .map(|value| value.trim().to_string())
+                    .unwrap_or_else(|_| "?".to_string())
+            });
+            holders.insert(target, format!("{}/{}", pid, comm));
+        }
+    }
+
+    holders
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_for_write(_root: &Path) -> HashMap<PathBuf, String> {
+    HashMap::new()
+}
+
+/// `flags:` in fdinfo is octal; the low two bits are the access mode (`O_WRONLY` = 1, `O_RDWR` = 2).
+#[cfg(target_os = "linux")]
+fn is_write_mode(fdinfo: &str) -> bool {
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .is_some_and(|flags| flags & 0o3 != 0)
+}
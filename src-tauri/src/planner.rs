@@ -1,10 +1,21 @@
 use crate::errors::AppResult;
-use crate::rules::{extension_lookup, normalize_extension, protected_top_level_folders, Rules};
+use crate::exclude::{ExcludeSet, SORTIGNORE_FILE_NAME};
+use crate::hash;
+use crate::openfiles;
+use crate::rules::{
+    extension_candidates, extension_lookup, filename_matchers, in_progress_matchers, match_bundle,
+    normalize_extension, protected_top_level_folders, CategoryRule, CollisionPolicy,
+    ContentSniffing, DuplicateAction, FilenameMatcher, Rules, SortMode, DUPLICATES_FOLDER,
+};
+use crate::sniff::{self, Signature};
+use crate::template;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 use walkdir::WalkDir;
 
@@ -12,9 +23,52 @@ use walkdir::WalkDir;
 #[serde(rename_all = "camelCase")]
 pub struct PlanEntry {
     pub source_path: String,
+    /// Empty for a duplicate sent to the Trash; the copy it keeps is `duplicate.original`.
     pub destination_path: String,
     pub category: String,
     pub collision_renamed: bool,
+    /// Signature label from content sniffing, when the file was sniffed and recognised.
+    #[serde(default)]
+    pub detected_type: Option<String>,
+    /// Name of the bundle rule when this entry moves a whole directory.
+    #[serde(default)]
+    pub bundle: Option<String>,
+    /// How the collision policy resolved an occupied destination, if there was one.
+    #[serde(default)]
+    pub collision: Option<CollisionDecision>,
+    /// Set when duplicate detection found a byte-identical file.
+    #[serde(default)]
+    pub duplicate: Option<DuplicateMark>,
+}
+
+/// A candidate whose contents match the existing file at its destination or an earlier
+/// candidate of the same plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateMark {
+    /// The file it duplicates: the occupied destination, or the earlier candidate's source.
+    pub original: String,
+    pub action: DuplicateAction,
+}
+
+/// What the executor does about an occupied destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionAction {
+    /// Moved under a free `name (n).ext` instead.
+    Renamed,
+    /// The existing file is trashed and the incoming one takes its place.
+    ReplacedExisting,
+    /// The incoming file is trashed and the existing one kept.
+    TrashedIncoming,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollisionDecision {
+    pub policy: CollisionPolicy,
+    pub action: CollisionAction,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,102 +94,455 @@ pub struct PlanPreview {
     pub total_candidates: u64,
     pub move_count: u64,
     pub skip_count: u64,
+    /// Skips that a later pass may move: too young, in-progress download or size not yet stable.
+    #[serde(default)]
+    pub pending_count: u64,
     pub error_count: u64,
     pub potential_conflicts: u64,
+    /// Moves marked as exact duplicates (see `PlanEntry::duplicate`).
+    #[serde(default)]
+    pub duplicate_count: u64,
     pub moves: Vec<PlanEntry>,
     pub skips: Vec<PlanSkip>,
     pub grouped: Vec<PlanGroup>,
 }
 
+/// What a dry run or run plans: `sort` moves new files into category folders, `library`
+/// re-sorts files already inside them (see `build_library_plan`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunMode {
+    #[default]
+    Sort,
+    Library,
+}
+
+/// Size and mtime observed per file on the previous planner pass, for `requireStableSize`.
+pub type SizeSamples = HashMap<PathBuf, (u64, SystemTime)>;
+
+/// How long a file must go unmodified before a one-off pass treats its size as stable.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// A tree the planner scans: the sort root itself, or an inbox folder feeding it.
+struct ScanSource {
+    root: PathBuf,
+    recursive: bool,
+    is_inbox: bool,
+}
+
 enum Classification {
     Target(String),
     Skip(String),
 }
 
+/// Plans a one-off pass such as a dry run or Run now. With `requireStableSize`, a file only
+/// moves once it has gone unmodified for `QUIET_PERIOD`; nothing is remembered between calls.
 pub fn build_plan(rules: &Rules) -> AppResult<PlanPreview> {
+    build_plan_impl(rules, None)
+}
+
+/// Like `build_plan`, but for repeated watcher passes: compares against and then replaces
+/// `samples` so `requireStableSize` holds back files whose size changed since the last pass.
+pub fn build_plan_with_samples(rules: &Rules, samples: &mut SizeSamples) -> AppResult<PlanPreview> {
+    build_plan_impl(rules, Some(samples))
+}
+
+fn build_plan_impl(rules: &Rules, mut samples: Option<&mut SizeSamples>) -> AppResult<PlanPreview> {
     let sort_root = PathBuf::from(&rules.global.sort_root);
-    let ext_map = extension_lookup(rules);
-    let protected = protected_top_level_folders(rules);
+    let mut protected = protected_top_level_folders(rules);
+    protected.insert(DUPLICATES_FOLDER.to_string());
 
-    let mut total_candidates = 0_u64;
-    let mut errors = 0_u64;
+    let mut sources = vec![ScanSource {
+        root: sort_root.clone(),
+        recursive: true,
+        is_inbox: false,
+    }];
+    sources.extend(rules.global.inbox_folders.iter().map(|inbox| ScanSource {
+        root: PathBuf::from(inbox.path.trim()),
+        recursive: inbox.recursive,
+        is_inbox: true,
+    }));
+
+    let previous_samples = samples.as_deref_mut().map(std::mem::take);
+    let roots: Vec<&Path> = sources.iter().map(|source| source.root.as_path()).collect();
+    let mut scanner = Scanner::new(rules, &roots, previous_samples);
     let mut potential_conflicts = 0_u64;
     let mut planned = Vec::new();
-    let mut skips = Vec::new();
     let mut reserved_destinations = HashSet::new();
+    let mut duplicates = DuplicateIndex::default();
+    let mut duplicate_count = 0_u64;
 
-    for entry in WalkDir::new(&sort_root).min_depth(1).into_iter() {
-        let entry = match entry {
-            Ok(value) => value,
-            Err(err) => {
-                errors += 1;
-                skips.push(PlanSkip {
-                    path: sort_root.to_string_lossy().to_string(),
-                    reason: err.to_string(),
-                });
-                continue;
-            }
-        };
-
-        if !entry.file_type().is_file() {
+    for source in &sources {
+        if source.is_inbox && !source.root.is_dir() {
+            scanner.skip(&source.root, "inbox folder does not exist".to_string());
             continue;
         }
 
-        let path = entry.path();
-        if is_inside_protected(path, &sort_root, &protected) {
-            continue;
+        let (mut excludes, root_ignore_err) = ExcludeSet::new(rules, &source.root);
+        if let Some(reason) = root_ignore_err {
+            scanner.errors += 1;
+            scanner.skip(&source.root.join(SORTIGNORE_FILE_NAME), reason);
         }
 
-        total_candidates += 1;
+        let max_depth = if source.recursive { usize::MAX } else { 1 };
+        let protected = Some((sort_root.as_path(), &protected));
+        for candidate in scanner.walk(&source.root, max_depth, &mut excludes, protected) {
+            let path = candidate.path.as_path();
+            let (detected, classification) = scanner.classify(&candidate);
+            let target_subfolder = match classification {
+                Classification::Target(target) => target,
+                Classification::Skip(reason) => {
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
 
-        if !is_old_enough(path, rules.global.min_file_age_seconds) {
-            skips.push(PlanSkip {
-                path: path.to_string_lossy().to_string(),
-                reason: format!(
-                    "file is younger than minFileAgeSeconds ({})",
-                    rules.global.min_file_age_seconds
-                ),
+            let Some(file_name) = path.file_name() else {
+                scanner.skip(path, "could not determine file name".to_string());
+                continue;
+            };
+
+            let ext_key = extension_key(path, rules, &scanner.ext_map);
+            let dest_dir = match template::expand(&target_subfolder, path, &ext_key) {
+                Ok(relative) => sort_root
+                    .join(relative)
+                    .join(drop_subpath(path, &source.root, rules.global.sort_mode)),
+                Err(reason) => {
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
+            let mut candidate_path = dest_dir.join(file_name);
+            let mut category = target_subfolder;
+            let mut policy = rules.global.collision_policy;
+            let bundle = candidate.bundle;
+
+            let duplicate = if rules.global.duplicate_detection.enabled && bundle.is_none() {
+                duplicates
+                    .find(path, &candidate_path)
+                    .map(|original| DuplicateMark {
+                        original: original.to_string_lossy().to_string(),
+                        action: rules.global.duplicate_detection.action,
+                    })
+            } else {
+                None
+            };
+            if let Some(mark) = duplicate.clone() {
+                duplicate_count += 1;
+                match mark.action {
+                    DuplicateAction::Keep => {}
+                    DuplicateAction::Trash => {
+                        // Trashed, not moved: the kept copy is `duplicate.original`.
+                        planned.push(PlanEntry {
+                            source_path: path.to_string_lossy().to_string(),
+                            destination_path: String::new(),
+                            category,
+                            collision_renamed: false,
+                            detected_type: detected.map(|signature| signature.label.to_string()),
+                            bundle: None,
+                            collision: None,
+                            duplicate,
+                        });
+                        continue;
+                    }
+                    DuplicateAction::MoveToDuplicates => {
+                        candidate_path = sort_root.join(DUPLICATES_FOLDER).join(file_name);
+                        category = DUPLICATES_FOLDER.to_string();
+                        policy = CollisionPolicy::Rename;
+                    }
+                }
+            }
+
+            let (dest_path, collision) = match resolve_destination(
+                path,
+                candidate_path,
+                &ext_key,
+                policy,
+                &mut reserved_destinations,
+            ) {
+                Destination::Move(dest_path, collision) => (dest_path, collision),
+                Destination::Skip(reason) => {
+                    potential_conflicts += 1;
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
+
+            if collision.is_some() {
+                potential_conflicts += 1;
+            }
+            let trashed = collision
+                .as_ref()
+                .is_some_and(|decision| decision.action == CollisionAction::TrashedIncoming);
+            if rules.global.duplicate_detection.enabled && duplicate.is_none() && !trashed {
+                duplicates.remember(path);
+            }
+
+            planned.push(PlanEntry {
+                source_path: path.to_string_lossy().to_string(),
+                destination_path: dest_path.to_string_lossy().to_string(),
+                category,
+                collision_renamed: is_renamed(&collision),
+                detected_type: detected.map(|signature| signature.label.to_string()),
+                bundle: bundle.map(|(name, _)| name),
+                collision,
+                duplicate,
             });
-            continue;
         }
+    }
 
-        let target_subfolder = match classify_target(path, rules, &ext_map) {
-            Classification::Target(target) => target,
-            Classification::Skip(reason) => {
-                skips.push(PlanSkip {
-                    path: path.to_string_lossy().to_string(),
-                    reason,
-                });
+    if let Some(samples) = samples {
+        *samples = std::mem::take(&mut scanner.next_samples);
+    }
+
+    Ok(scanner.finish(planned, potential_conflicts, duplicate_count))
+}
+
+/// A file, or a directory matching a bundle rule, that passed every gate of a walk.
+struct ScanCandidate {
+    path: PathBuf,
+    /// Bundle name and target subfolder when `path` is a bundle directory.
+    bundle: Option<(String, String)>,
+}
+
+/// Walks trees for the sort and library planners, applying the same exclusions and gates
+/// (in-progress names, minimum age, open files, stable size) to both, and tallying what
+/// it skipped.
+struct Scanner<'a> {
+    rules: &'a Rules,
+    ext_map: HashMap<String, String>,
+    matchers: Vec<FilenameMatcher>,
+    in_progress: Vec<(String, glob::Pattern)>,
+    open_files: HashMap<PathBuf, String>,
+    /// `false` when every walked file is a candidate whatever its age or state.
+    gates: bool,
+    /// Sizes seen on the previous watcher pass; `None` for one-off passes.
+    previous_samples: Option<SizeSamples>,
+    next_samples: SizeSamples,
+    total_candidates: u64,
+    pending: u64,
+    errors: u64,
+    skips: Vec<PlanSkip>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(rules: &'a Rules, roots: &[&Path], previous_samples: Option<SizeSamples>) -> Self {
+        let open_files = if rules.global.skip_open_files {
+            roots.iter().flat_map(|root| openfiles::open_for_write(root)).collect()
+        } else {
+            HashMap::new()
+        };
+        Self {
+            rules,
+            ext_map: extension_lookup(rules),
+            matchers: filename_matchers(rules),
+            in_progress: in_progress_matchers(rules),
+            open_files,
+            gates: true,
+            previous_samples,
+            next_samples: SizeSamples::new(),
+            total_candidates: 0,
+            pending: 0,
+            errors: 0,
+            skips: Vec::new(),
+        }
+    }
+
+    /// A scanner for previews that ask how rules classify files, not whether a run would move
+    /// them now.
+    fn without_gates(rules: &'a Rules) -> Self {
+        let mut scanner = Self::new(rules, &[], None);
+        scanner.gates = false;
+        scanner
+    }
+
+    fn skip(&mut self, path: &Path, reason: String) {
+        self.skips.push(PlanSkip {
+            path: path.to_string_lossy().to_string(),
+            reason,
+        });
+    }
+
+    fn hold(&mut self, path: &Path, reason: String) {
+        self.pending += 1;
+        self.skip(path, reason);
+    }
+
+    /// Files and bundles below `root` that are ready to move. With `protected`, top-level
+    /// folders of that sort root are not entered.
+    fn walk(
+        &mut self,
+        root: &Path,
+        max_depth: usize,
+        excludes: &mut ExcludeSet,
+        protected: Option<(&Path, &HashSet<String>)>,
+    ) -> Vec<ScanCandidate> {
+        let mut candidates = Vec::new();
+        let mut walker = WalkDir::new(root).min_depth(1).max_depth(max_depth).into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(value) => value,
+                Err(err) => {
+                    self.errors += 1;
+                    self.skip(root, err.to_string());
+                    continue;
+                }
+            };
+
+            let is_dir = entry.file_type().is_dir();
+            if !is_dir && !entry.file_type().is_file() {
                 continue;
             }
-        };
 
-        let Some(file_name) = path.file_name() else {
-            skips.push(PlanSkip {
-                path: path.to_string_lossy().to_string(),
-                reason: "could not determine file name".to_string(),
-            });
-            continue;
-        };
+            let path = entry.path();
+            let protected_path = protected
+                .is_some_and(|(sort_root, folders)| is_inside_protected(path, sort_root, folders));
+            if protected_path {
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
 
-        let dest_dir = sort_root.join(&target_subfolder);
-        let candidate = dest_dir.join(file_name);
-        let (dest_path, renamed) = resolve_destination(candidate, &mut reserved_destinations);
+            if !is_dir && entry.file_name() == SORTIGNORE_FILE_NAME {
+                continue;
+            }
 
-        if renamed {
-            potential_conflicts += 1;
+            if let Some(reason) = excludes.excluded_by(path, is_dir) {
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                self.skip(path, reason);
+                continue;
+            }
+
+            if is_dir {
+                if let Some(reason) = excludes.load_dir(path) {
+                    self.errors += 1;
+                    self.skip(&path.join(SORTIGNORE_FILE_NAME), reason);
+                }
+            }
+
+            // A recognised bundle is planned as one directory move; its contents are never visited.
+            let bundle = if is_dir {
+                let Some((bundle, target)) = match_bundle(self.rules, path) else {
+                    continue;
+                };
+                walker.skip_current_dir();
+                Some((bundle.name.clone(), target.to_string()))
+            } else {
+                None
+            };
+
+            self.total_candidates += 1;
+            if !self.gates || self.passes_gates(path, bundle.is_some()) {
+                candidates.push(ScanCandidate {
+                    path: path.to_path_buf(),
+                    bundle,
+                });
+            }
+        }
+        candidates
+    }
+
+    /// Holds back files that may still be changing; records why as a skip, pending for every
+    /// gate but the in-progress names.
+    fn passes_gates(&mut self, path: &Path, is_bundle: bool) -> bool {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(pattern) = in_progress_match(&file_name, &self.in_progress) {
+            // Not held: finishing or renaming the download raises its own watcher event.
+            let reason = format!("looks like an in-progress download or lock file ('{}')", pattern);
+            self.skip(path, reason);
+            return false;
         }
 
-        planned.push(PlanEntry {
-            source_path: path.to_string_lossy().to_string(),
-            destination_path: dest_path.to_string_lossy().to_string(),
-            category: target_subfolder,
-            collision_renamed: renamed,
-        });
+        let min_age = self.rules.global.min_file_age_seconds;
+        if is_bundle && !is_tree_old_enough(path, min_age) {
+            self.hold(
+                path,
+                format!("bundle contains files younger than minFileAgeSeconds ({})", min_age),
+            );
+            return false;
+        }
+        if !is_bundle && !is_old_enough(path, min_age) {
+            self.hold(path, format!("file is younger than minFileAgeSeconds ({})", min_age));
+            return false;
+        }
+
+        if let Some(holder) = open_holder(path, is_bundle, &self.open_files) {
+            let reason = format!("in use by {}", holder);
+            self.hold(path, reason);
+            return false;
+        }
+
+        if self.rules.global.require_stable_size && !is_bundle {
+            let current = match sample_size(path) {
+                Ok(current) => current,
+                Err(err) => {
+                    self.errors += 1;
+                    self.skip(path, format!("could not read size: {}", err));
+                    return false;
+                }
+            };
+            let stable = match &self.previous_samples {
+                Some(previous) => {
+                    self.next_samples.insert(path.to_path_buf(), current);
+                    previous.get(path) == Some(&current)
+                }
+                None => current.1.elapsed().is_ok_and(|age| age >= QUIET_PERIOD),
+            };
+            if !stable {
+                self.hold(
+                    path,
+                    format!("waiting for size to stabilise ({} bytes this pass)", current.0),
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// The category target for a candidate, sniffing its content when the rules call for it.
+    /// Bundles go where their rule says.
+    fn classify(&self, candidate: &ScanCandidate) -> (Option<Signature>, Classification) {
+        if let Some((_, target)) = &candidate.bundle {
+            return (None, Classification::Target(target.clone()));
+        }
+        let path = candidate.path.as_path();
+        let detected = sniff_if_needed(path, self.rules, &self.ext_map);
+        let classification =
+            classify_target(path, self.rules, &self.ext_map, &self.matchers, detected);
+        (detected, classification)
+    }
+
+    fn finish(
+        self,
+        planned: Vec<PlanEntry>,
+        potential_conflicts: u64,
+        duplicate_count: u64,
+    ) -> PlanPreview {
+        let grouped = group_by_category(&planned);
+        PlanPreview {
+            session_id: Uuid::new_v4().to_string(),
+            generated_at: Utc::now().to_rfc3339(),
+            total_candidates: self.total_candidates,
+            move_count: planned.len() as u64,
+            skip_count: self.skips.len() as u64,
+            pending_count: self.pending,
+            error_count: self.errors,
+            potential_conflicts,
+            duplicate_count,
+            moves: planned,
+            skips: self.skips,
+            grouped,
+        }
     }
+}
 
+fn group_by_category(planned: &[PlanEntry]) -> Vec<PlanGroup> {
     let mut grouped_map: HashMap<String, Vec<PlanEntry>> = HashMap::new();
-    for entry in &planned {
+    for entry in planned {
         grouped_map
             .entry(entry.category.clone())
             .or_default()
@@ -151,65 +558,871 @@ pub fn build_plan(rules: &Rules) -> AppResult<PlanPreview> {
         })
         .collect();
     grouped.sort_by(|a, b| a.category.cmp(&b.category));
+    grouped
+}
+
+/// Plans moves for files already inside the category folders named in `library_folders`
+/// whose category under `rules` now lives elsewhere, e.g. after an extension moved between
+/// categories or a category was renamed.
+///
+/// A file stays put when it is already below its expanded `targetSubfolder`. Files that
+/// move are flattened into the new category folder; bundles move intact. Exclusions and
+/// the in-progress, age, open-file and stable-size gates apply as in a one-off run.
+pub fn build_library_plan(rules: &Rules, library_folders: &HashSet<String>) -> AppResult<PlanPreview> {
+    let sort_root = PathBuf::from(&rules.global.sort_root);
+    let mut scanner = Scanner::new(rules, &[sort_root.as_path()], None);
+    let (mut excludes, root_ignore_err) = ExcludeSet::new(rules, &sort_root);
+    if let Some(reason) = root_ignore_err {
+        scanner.errors += 1;
+        scanner.skip(&sort_root.join(SORTIGNORE_FILE_NAME), reason);
+    }
+
+    let mut folders: Vec<&String> = library_folders.iter().collect();
+    folders.sort();
+
+    let mut potential_conflicts = 0_u64;
+    let mut planned = Vec::new();
+    let mut reserved_destinations = HashSet::new();
+
+    for folder in folders {
+        let library_root = sort_root.join(folder);
+        if !library_root.is_dir() {
+            continue;
+        }
+
+        for candidate in scanner.walk(&library_root, usize::MAX, &mut excludes, None) {
+            let path = candidate.path.as_path();
+            let (detected, classification) = scanner.classify(&candidate);
+            let target_subfolder = match classification {
+                Classification::Target(target) => target,
+                Classification::Skip(reason) => {
+                    scanner.skip(path, format!("{}; left in place", reason));
+                    continue;
+                }
+            };
+
+            let ext_key = extension_key(path, rules, &scanner.ext_map);
+            let target_dir = match template::expand(&target_subfolder, path, &ext_key) {
+                Ok(relative) => sort_root.join(relative),
+                Err(reason) => {
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
+            if path.starts_with(&target_dir) {
+                continue;
+            }
+
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+            let (dest_path, collision) = match resolve_destination(
+                path,
+                target_dir.join(file_name),
+                &ext_key,
+                rules.global.collision_policy,
+                &mut reserved_destinations,
+            ) {
+                Destination::Move(dest_path, collision) => (dest_path, collision),
+                Destination::Skip(reason) => {
+                    potential_conflicts += 1;
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
+            if collision.is_some() {
+                potential_conflicts += 1;
+            }
+
+            planned.push(PlanEntry {
+                source_path: path.to_string_lossy().to_string(),
+                destination_path: dest_path.to_string_lossy().to_string(),
+                category: target_subfolder,
+                collision_renamed: is_renamed(&collision),
+                detected_type: detected.map(|signature| signature.label.to_string()),
+                bundle: candidate.bundle.map(|(name, _)| name),
+                collision,
+                duplicate: None,
+            });
+        }
+    }
+
+    Ok(scanner.finish(planned, potential_conflicts, 0))
+}
+
+/// Plans moving the library to `new_root`: every entry directly inside an existing
+/// protected category folder moves to the same folder under `new_root`. Entries that collide
+/// with something already there are always renamed, whatever the collision policy.
+pub fn build_relocation_plan(rules: &Rules, new_root: &Path) -> AppResult<PlanPreview> {
+    let sort_root = PathBuf::from(&rules.global.sort_root);
+    let ext_map = extension_lookup(rules);
+
+    let mut folders: Vec<String> = protected_top_level_folders(rules).into_iter().collect();
+    folders.push(DUPLICATES_FOLDER.to_string());
+    folders.sort();
+
+    let mut planned = Vec::new();
+    let mut skips = Vec::new();
+    let mut reserved_destinations = HashSet::new();
+    let mut potential_conflicts = 0_u64;
 
+    for folder in folders {
+        let old_dir = sort_root.join(&folder);
+        if !old_dir.is_dir() {
+            continue;
+        }
+
+        let mut children: Vec<PathBuf> = fs::read_dir(&old_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        children.sort();
+
+        for path in children {
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+            let ext_key = if path.is_dir() {
+                String::new()
+            } else {
+                extension_key(&path, rules, &ext_map)
+            };
+            let (dest_path, collision) = match resolve_destination(
+                &path,
+                new_root.join(&folder).join(file_name),
+                &ext_key,
+                CollisionPolicy::Rename,
+                &mut reserved_destinations,
+            ) {
+                Destination::Move(dest_path, collision) => (dest_path, collision),
+                Destination::Skip(reason) => {
+                    potential_conflicts += 1;
+                    skips.push(PlanSkip {
+                        path: path.to_string_lossy().to_string(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+            if collision.is_some() {
+                potential_conflicts += 1;
+            }
+
+            planned.push(PlanEntry {
+                source_path: path.to_string_lossy().to_string(),
+                destination_path: dest_path.to_string_lossy().to_string(),
+                category: folder.clone(),
+                collision_renamed: is_renamed(&collision),
+                detected_type: None,
+                bundle: None,
+                collision,
+                duplicate: None,
+            });
+        }
+    }
+
+    let grouped = group_by_category(&planned);
     Ok(PlanPreview {
         session_id: Uuid::new_v4().to_string(),
         generated_at: Utc::now().to_rfc3339(),
-        total_candidates,
+        total_candidates: planned.len() as u64 + skips.len() as u64,
         move_count: planned.len() as u64,
         skip_count: skips.len() as u64,
-        error_count: errors,
+        pending_count: 0,
+        error_count: 0,
         potential_conflicts,
+        duplicate_count: 0,
         moves: planned,
         skips,
         grouped,
     })
 }
 
-fn classify_target(path: &Path, rules: &Rules, ext_map: &HashMap<String, String>) -> Classification {
-    let ext = path
-        .extension()
-        .map(|x| x.to_string_lossy().to_string())
-        .unwrap_or_default();
+/// A file an undo restored, and where the undone run had moved it.
+#[derive(Debug, Clone)]
+pub struct RedoCandidate {
+    pub restored_path: PathBuf,
+    pub new_path: PathBuf,
+    pub bundle: Option<String>,
+}
+
+/// Plans moving restored files back to their journaled `new_path`. An occupied destination
+/// is resolved by the collision policy as a sort would, except that a restored file is never
+/// trashed: where the policy would discard it, it is renamed instead. Restored files that are
+/// gone are skipped.
+pub fn build_redo_plan(rules: &Rules, candidates: &[RedoCandidate]) -> PlanPreview {
+    let sort_root = PathBuf::from(&rules.global.sort_root);
+    let ext_map = extension_lookup(rules);
+
+    let mut planned = Vec::new();
+    let mut skips = Vec::new();
+    let mut reserved_destinations = HashSet::new();
+    let mut potential_conflicts = 0_u64;
 
-    if ext.is_empty() {
-        return if rules.global.no_extension_goes_to_misc {
-            Classification::Target(rules.misc.target_subfolder.clone())
+    for candidate in candidates {
+        let source = &candidate.restored_path;
+        if fs::symlink_metadata(source).is_err() {
+            skips.push(PlanSkip {
+                path: source.to_string_lossy().to_string(),
+                reason: "restored file no longer exists".to_string(),
+            });
+            continue;
+        }
+        let ext_key = if source.is_dir() {
+            String::new()
         } else {
-            Classification::Skip("no extension and noExtensionGoesToMisc=false".to_string())
+            extension_key(source, rules, &ext_map)
+        };
+        let policy = rules.global.collision_policy;
+        let destination = match resolve_destination(
+            source,
+            candidate.new_path.clone(),
+            &ext_key,
+            policy,
+            &mut reserved_destinations,
+        ) {
+            Destination::Move(dest_path, Some(decision))
+                if decision.action == CollisionAction::TrashedIncoming =>
+            {
+                rename_destination(
+                    dest_path,
+                    &ext_key,
+                    policy,
+                    "redo never trashes a restored file",
+                    &mut reserved_destinations,
+                )
+            }
+            destination => destination,
+        };
+        let (dest_path, collision) = match destination {
+            Destination::Move(dest_path, collision) => (dest_path, collision),
+            Destination::Skip(reason) => {
+                potential_conflicts += 1;
+                skips.push(PlanSkip {
+                    path: source.to_string_lossy().to_string(),
+                    reason,
+                });
+                continue;
+            }
         };
+        if collision.is_some() {
+            potential_conflicts += 1;
+        }
+
+        let category = dest_path
+            .strip_prefix(&sort_root)
+            .ok()
+            .and_then(|relative| relative.iter().next())
+            .map(|first| first.to_string_lossy().to_string())
+            .unwrap_or_default();
+        planned.push(PlanEntry {
+            source_path: source.to_string_lossy().to_string(),
+            destination_path: dest_path.to_string_lossy().to_string(),
+            category,
+            collision_renamed: is_renamed(&collision),
+            detected_type: None,
+            bundle: candidate.bundle.clone(),
+            collision,
+            duplicate: None,
+        });
     }
 
-    let key = normalize_extension(&ext, rules.global.case_insensitive_ext);
-    if let Some(target) = ext_map.get(&key) {
-        return Classification::Target(target.clone());
+    let grouped = group_by_category(&planned);
+    PlanPreview {
+        session_id: Uuid::new_v4().to_string(),
+        generated_at: Utc::now().to_rfc3339(),
+        total_candidates: planned.len() as u64 + skips.len() as u64,
+        move_count: planned.len() as u64,
+        skip_count: skips.len() as u64,
+        pending_count: 0,
+        error_count: 0,
+        potential_conflicts,
+        duplicate_count: 0,
+        moves: planned,
+        skips,
+        grouped,
+    }
+}
+
+/// A library file whose category would change under candidate rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReclassifiedFile {
+    pub path: String,
+    /// Top-level category folder the file is in now.
+    pub from_folder: String,
+    /// Top-level category folder it would move to.
+    pub to_folder: String,
+    /// The candidate rules' expanded target folder. Collisions there are not resolved.
+    pub target_folder: String,
+}
+
+/// Files a category folder would gain from and lose to other folders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryImpact {
+    pub folder: String,
+    pub gained: u64,
+    pub lost: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleImpact {
+    pub files_examined: u64,
+    pub reclassified: Vec<ReclassifiedFile>,
+    pub categories: Vec<CategoryImpact>,
+    pub skips: Vec<PlanSkip>,
+}
+
+/// Classifies the library under both the current and the candidate rules' category folders
+/// using `candidate`, and reports what would change. Nothing is moved. The library is the
+/// one at the current sort root, whatever root `candidate` names. The report depends only
+/// on the rules: the run gates and the collision policy do not apply.
+pub fn rule_impact(current: &Rules, candidate: &Rules) -> AppResult<RuleImpact> {
+    let sort_root = PathBuf::from(&current.global.sort_root);
+    let mut library_folders: Vec<String> = protected_top_level_folders(current)
+        .union(&protected_top_level_folders(candidate))
+        .cloned()
+        .collect();
+    library_folders.sort();
+
+    let mut scanner = Scanner::without_gates(candidate);
+    let (mut excludes, root_ignore_err) = ExcludeSet::new(candidate, &sort_root);
+    if let Some(reason) = root_ignore_err {
+        scanner.errors += 1;
+        scanner.skip(&sort_root.join(SORTIGNORE_FILE_NAME), reason);
+    }
+
+    let mut per_folder: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut reclassified = Vec::new();
+
+    for from_folder in library_folders {
+        let library_root = sort_root.join(&from_folder);
+        if !library_root.is_dir() {
+            continue;
+        }
+
+        for file in scanner.walk(&library_root, usize::MAX, &mut excludes, None) {
+            let path = file.path.as_path();
+            let target_subfolder = match scanner.classify(&file).1 {
+                Classification::Target(target) => target,
+                Classification::Skip(reason) => {
+                    scanner.skip(path, format!("{}; left in place", reason));
+                    continue;
+                }
+            };
+
+            let ext_key = extension_key(path, candidate, &scanner.ext_map);
+            let target_dir = match template::expand(&target_subfolder, path, &ext_key) {
+                Ok(relative) => sort_root.join(relative),
+                Err(reason) => {
+                    scanner.skip(path, reason);
+                    continue;
+                }
+            };
+            if path.starts_with(&target_dir) {
+                continue;
+            }
+
+            let to_folder = template::top_level_folder(&target_subfolder);
+            // Moving between subfolders of one top-level folder is neither a gain nor a loss.
+            if from_folder != to_folder {
+                per_folder.entry(from_folder.clone()).or_default().1 += 1;
+                per_folder.entry(to_folder.clone()).or_default().0 += 1;
+            }
+            reclassified.push(ReclassifiedFile {
+                path: path.to_string_lossy().to_string(),
+                from_folder: from_folder.clone(),
+                to_folder,
+                target_folder: target_dir.to_string_lossy().to_string(),
+            });
+        }
+    }
+
+    let mut categories: Vec<CategoryImpact> = per_folder
+        .into_iter()
+        .map(|(folder, (gained, lost))| CategoryImpact {
+            folder,
+            gained,
+            lost,
+        })
+        .collect();
+    categories.sort_by(|a, b| a.folder.cmp(&b.folder));
+
+    Ok(RuleImpact {
+        files_examined: scanner.total_candidates,
+        reclassified,
+        categories,
+        skips: scanner.skips,
+    })
+}
+
+/// One rule that matches a file, in the order `classify_target` consults them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleMatch {
+    /// `bundle`, `filenameGlob`, `filenameRegex`, `contentSignature`, `extension` or `misc`.
+    pub kind: String,
+    /// `None` for the `Misc` fallback.
+    pub category_id: Option<String>,
+    pub category_name: String,
+    pub target_subfolder: String,
+    /// The pattern, extension, signature label or flag that matched.
+    pub detail: String,
+}
+
+/// An extension of the explained file listed by several categories; only `winner` applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionShadow {
+    pub extension: String,
+    pub winner: String,
+    pub shadowed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassificationExplanation {
+    pub path: String,
+    /// Normalized extension used for lookup (longest suffix with a rule).
+    pub extension: String,
+    pub detected_type: Option<String>,
+    /// The rule that decides the file's category; `None` when it would be skipped.
+    pub matched: Option<RuleMatch>,
+    /// The rule that would decide instead if `matched` were removed.
+    pub next: Option<RuleMatch>,
+    pub skip_reason: Option<String>,
+    pub case_insensitive_ext: bool,
+    pub unknown_goes_to_misc: bool,
+    pub no_extension_goes_to_misc: bool,
+    pub content_sniffing: ContentSniffing,
+    pub min_file_age_seconds: u64,
+    pub age_seconds: Option<u64>,
+    pub old_enough: bool,
+    pub shadowed_extensions: Vec<ExtensionShadow>,
+}
+
+/// Explains how `path` would be classified under `rules`, without moving anything.
+///
+/// Lists every matching rule in precedence order: the first decides, the second is what
+/// would apply next. Directories are only explained as bundles.
+pub fn explain_classification(rules: &Rules, path: &Path) -> AppResult<ClassificationExplanation> {
+    let metadata = fs::metadata(path)?;
+    let ext_map = extension_lookup(rules);
+    let matchers = filename_matchers(rules);
+
+    let mut candidates = Vec::new();
+    let mut skip_reason = None;
+    let mut key = String::new();
+    let mut detected = None;
+    let mut shadowed_extensions = Vec::new();
+
+    if metadata.is_dir() {
+        match match_bundle(rules, path) {
+            Some((bundle, _)) => {
+                candidates.push(category_match(rules, &bundle.category_id, "bundle", &bundle.name))
+            }
+            None => skip_reason = Some("directory does not match any bundle rule".to_string()),
+        }
+    } else {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        key = extension_key(path, rules, &ext_map);
+        detected = sniff_if_needed(path, rules, &ext_map);
+        candidates = matching_rules(path, rules, &ext_map, &matchers, detected, 2);
+        if candidates.is_empty() {
+            skip_reason = Some(no_match_reason(&key));
+        }
+
+        for ext in extension_candidates(&file_name) {
+            let ext = normalize_extension(ext, rules.global.case_insensitive_ext);
+            let listing: Vec<_> = rules
+                .categories
+                .iter()
+                .filter(|category| lists_extension(rules, category, &ext))
+                .collect();
+            if listing.len() > 1 {
+                shadowed_extensions.push(ExtensionShadow {
+                    extension: ext,
+                    winner: listing[0].name.clone(),
+                    shadowed: listing[1..].iter().map(|category| category.name.clone()).collect(),
+                });
+            }
+        }
+    }
+
+    let age_seconds = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map(|age| age.as_secs());
+    let min_age = rules.global.min_file_age_seconds;
+    let old_enough = if metadata.is_dir() {
+        is_tree_old_enough(path, min_age)
+    } else {
+        is_old_enough(path, min_age)
+    };
+
+    let mut candidates = candidates.into_iter();
+    Ok(ClassificationExplanation {
+        path: path.to_string_lossy().to_string(),
+        extension: key,
+        detected_type: detected.map(|signature| signature.label.to_string()),
+        matched: candidates.next(),
+        next: candidates.next(),
+        skip_reason,
+        case_insensitive_ext: rules.global.case_insensitive_ext,
+        unknown_goes_to_misc: rules.global.unknown_goes_to_misc,
+        no_extension_goes_to_misc: rules.global.no_extension_goes_to_misc,
+        content_sniffing: rules.global.content_sniffing,
+        min_file_age_seconds: min_age,
+        age_seconds,
+        old_enough,
+        shadowed_extensions,
+    })
+}
+
+fn category_match(rules: &Rules, category_id: &str, kind: &str, detail: &str) -> RuleMatch {
+    let category = rules.categories.iter().find(|category| category.id == category_id);
+    RuleMatch {
+        kind: kind.to_string(),
+        category_id: Some(category_id.to_string()),
+        category_name: category.map(|category| category.name.clone()).unwrap_or_default(),
+        target_subfolder: category
+            .map(|category| category.target_subfolder.clone())
+            .unwrap_or_default(),
+        detail: detail.to_string(),
+    }
+}
+
+/// The category a content signature resolves to: the first one listing any of the
+/// signature's extensions.
+fn signature_category(signature: Signature, rules: &Rules) -> Option<&CategoryRule> {
+    signature.extensions.iter().find_map(|ext| {
+        let key = normalize_extension(ext, rules.global.case_insensitive_ext);
+        rules
+            .categories
+            .iter()
+            .find(|category| lists_extension(rules, category, &key))
+    })
+}
+
+fn classify_target(
+    path: &Path,
+    rules: &Rules,
+    ext_map: &HashMap<String, String>,
+    matchers: &[FilenameMatcher],
+    detected: Option<Signature>,
+) -> Classification {
+    match matching_rules(path, rules, ext_map, matchers, detected, 1).pop() {
+        Some(rule) => Classification::Target(rule.target_subfolder),
+        None => Classification::Skip(no_match_reason(&extension_key(path, rules, ext_map))),
+    }
+}
+
+/// The rules that match the file at `path`, in precedence order, stopping after `limit`.
+/// The first one decides the category; `classify_target` and `explain_classification`
+/// both read this list, so the explanation always agrees with the planner.
+fn matching_rules(
+    path: &Path,
+    rules: &Rules,
+    ext_map: &HashMap<String, String>,
+    matchers: &[FilenameMatcher],
+    detected: Option<Signature>,
+    limit: usize,
+) -> Vec<RuleMatch> {
+    let mut found = Vec::new();
+    let file_name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Filename patterns are more specific than extensions, so they are checked first.
+    for matcher in matchers {
+        for (kind, pattern) in matcher.matching_patterns(&file_name) {
+            found.push(category_match(rules, &matcher.category_id, kind, pattern));
+            if found.len() >= limit {
+                return found;
+            }
+        }
+    }
+
+    let key = extension_key(path, rules, ext_map);
+    let sniffed = detected.and_then(|signature| {
+        signature_category(signature, rules).map(|category| RuleMatch {
+            kind: "contentSignature".to_string(),
+            category_id: Some(category.id.clone()),
+            category_name: category.name.clone(),
+            target_subfolder: category.target_subfolder.clone(),
+            detail: signature.label.to_string(),
+        })
+    });
+    let sniff_overrides = rules.global.content_sniffing == ContentSniffing::Override
+        && !detected.is_some_and(|signature| {
+            signature
+                .extensions
+                .iter()
+                .any(|ext| normalize_extension(ext, rules.global.case_insensitive_ext) == key)
+        });
+    let sniffed = if sniff_overrides {
+        found.extend(sniffed);
+        None
+    } else {
+        sniffed
+    };
+
+    // Every suffix with a rule, longest first; the first category listing one wins.
+    for ext in extension_candidates(&file_name) {
+        let ext = normalize_extension(ext, rules.global.case_insensitive_ext);
+        if !ext_map.contains_key(&ext) {
+            continue;
+        }
+        for category in &rules.categories {
+            if found.len() >= limit {
+                return found;
+            }
+            if lists_extension(rules, category, &ext) {
+                found.push(category_match(rules, &category.id, "extension", &ext));
+            }
+        }
+    }
+
+    found.extend(sniffed);
+
+    let misc_flag = if key.is_empty() {
+        rules.global.no_extension_goes_to_misc.then_some("noExtensionGoesToMisc")
+    } else {
+        rules.global.unknown_goes_to_misc.then_some("unknownGoesToMisc")
+    };
+    if let Some(flag) = misc_flag {
+        found.push(RuleMatch {
+            kind: "misc".to_string(),
+            category_id: None,
+            category_name: rules.misc.name.clone(),
+            target_subfolder: rules.misc.target_subfolder.clone(),
+            detail: flag.to_string(),
+        });
+    }
+
+    found.truncate(limit);
+    found
+}
+
+/// Why a file that no rule matches is skipped.
+fn no_match_reason(key: &str) -> String {
+    if key.is_empty() {
+        "no extension and noExtensionGoesToMisc=false".to_string()
+    } else {
+        format!("unknown extension '.{}' and unknownGoesToMisc=false", key)
     }
+}
+
+fn lists_extension(rules: &Rules, category: &CategoryRule, ext: &str) -> bool {
+    category
+        .extensions
+        .iter()
+        .any(|listed| normalize_extension(listed, rules.global.case_insensitive_ext) == ext)
+}
+
+/// The normalized extension a file is classified by: the longest suffix that has a rule
+/// (`tar.gz` before `gz`), otherwise the last dot-separated suffix.
+fn extension_key(path: &Path, rules: &Rules, ext_map: &HashMap<String, String>) -> String {
+    let file_name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let candidates: Vec<String> = extension_candidates(&file_name)
+        .into_iter()
+        .map(|ext| normalize_extension(ext, rules.global.case_insensitive_ext))
+        .collect();
+
+    candidates
+        .iter()
+        .find(|key| ext_map.contains_key(*key))
+        .or_else(|| candidates.last())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Sniffs only when the configured mode could act on the result, so `fallback` mode
+/// never opens files whose extension is already known.
+fn sniff_if_needed(path: &Path, rules: &Rules, ext_map: &HashMap<String, String>) -> Option<Signature> {
+    let needed = match rules.global.content_sniffing {
+        ContentSniffing::Off => false,
+        ContentSniffing::Fallback => !ext_map.contains_key(&extension_key(path, rules, ext_map)),
+        ContentSniffing::Override => true,
+    };
 
-    if rules.global.unknown_goes_to_misc {
-        Classification::Target(rules.misc.target_subfolder.clone())
+    if needed {
+        sniff::detect(path)
     } else {
-        Classification::Skip(format!("unknown extension '.{}' and unknownGoesToMisc=false", key))
+        None
+    }
+}
+
+/// Candidates seen so far in one plan, by size. Contents are hashed lazily, once a second
+/// file of the same size turns up.
+#[derive(Default)]
+struct DuplicateIndex {
+    by_size: HashMap<u64, Vec<(PathBuf, Option<String>)>>,
+}
+
+impl DuplicateIndex {
+    /// Returns what `path` duplicates: the existing file at `destination`, else an earlier
+    /// candidate passed to `remember`.
+    fn find(&mut self, path: &Path, destination: &Path) -> Option<PathBuf> {
+        if destination.exists() && hash::files_identical(path, destination) {
+            return Some(destination.to_path_buf());
+        }
+
+        let size = fs::metadata(path).ok().filter(|meta| meta.is_file())?.len();
+        let seen = self.by_size.get_mut(&size)?;
+        let digest = hash::file_digest(path).ok()?;
+        for (other, other_digest) in seen.iter_mut() {
+            if other_digest.is_none() {
+                *other_digest = hash::file_digest(other).ok();
+            }
+            if other_digest.as_ref() == Some(&digest) {
+                return Some(other.clone());
+            }
+        }
+        None
+    }
+
+    /// Makes `path` an original for later candidates. Only files that really move into the
+    /// library qualify: one its collision trashes must not take an identical copy with it.
+    fn remember(&mut self, path: &Path) {
+        if let Some(meta) = fs::metadata(path).ok().filter(|meta| meta.is_file()) {
+            self.by_size
+                .entry(meta.len())
+                .or_default()
+                .push((path.to_path_buf(), None));
+        }
     }
 }
 
-fn resolve_destination(candidate: PathBuf, reserved: &mut HashSet<PathBuf>) -> (PathBuf, bool) {
-    if !candidate.exists() && !reserved.contains(&candidate) {
+/// Where `resolve_destination` sends an entry.
+enum Destination {
+    Move(PathBuf, Option<CollisionDecision>),
+    Skip(String),
+}
+
+/// Applies the collision policy when `candidate` already exists on disk. A name claimed
+/// earlier in the same plan, a file meeting a directory, or a comparison the policy cannot
+/// make (sizes and hashes of directories, unreadable metadata), falls back to a rename.
+fn resolve_destination(
+    source: &Path,
+    candidate: PathBuf,
+    ext_key: &str,
+    policy: CollisionPolicy,
+    reserved: &mut HashSet<PathBuf>,
+) -> Destination {
+    if reserved.contains(&candidate) {
+        return rename_destination(
+            candidate,
+            ext_key,
+            policy,
+            "name already taken in this run",
+            reserved,
+        );
+    }
+    if !candidate.exists() {
         reserved.insert(candidate.clone());
-        return (candidate, false);
+        return Destination::Move(candidate, None);
     }
 
+    let is_dir = |path: &Path| fs::metadata(path).map(|meta| meta.is_dir()).ok();
+    let same_kind = matches!((is_dir(source), is_dir(&candidate)), (Some(a), Some(b)) if a == b);
+
+    let outcome = match policy {
+        CollisionPolicy::Rename => None,
+        CollisionPolicy::Skip => {
+            return Destination::Skip(format!(
+                "'{}' already exists (collision policy: skip)",
+                candidate.to_string_lossy()
+            ));
+        }
+        // Never trash a directory to make room for a file, or the other way round.
+        _ if !same_kind => {
+            return rename_destination(
+                candidate,
+                ext_key,
+                policy,
+                "destination exists and is not the same kind (file or directory)",
+                reserved,
+            );
+        }
+        CollisionPolicy::OverwriteToTrash => Some((
+            CollisionAction::ReplacedExisting,
+            "existing file moved to Trash",
+        )),
+        CollisionPolicy::KeepNewer => {
+            let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+            match (modified(source), modified(&candidate)) {
+                (Some(incoming), Some(existing)) if incoming > existing => {
+                    Some((CollisionAction::ReplacedExisting, "incoming file is newer"))
+                }
+                (Some(_), Some(_)) => Some((
+                    CollisionAction::TrashedIncoming,
+                    "existing file is at least as new",
+                )),
+                _ => None,
+            }
+        }
+        CollisionPolicy::KeepLarger => {
+            let size = |path: &Path| {
+                fs::metadata(path)
+                    .ok()
+                    .filter(|meta| meta.is_file())
+                    .map(|meta| meta.len())
+            };
+            match (size(source), size(&candidate)) {
+                (Some(incoming), Some(existing)) if incoming > existing => {
+                    Some((CollisionAction::ReplacedExisting, "incoming file is larger"))
+                }
+                (Some(_), Some(_)) => Some((
+                    CollisionAction::TrashedIncoming,
+                    "existing file is at least as large",
+                )),
+                _ => None,
+            }
+        }
+        CollisionPolicy::DedupeIfIdentical => hash::files_identical(source, &candidate)
+            .then_some((CollisionAction::TrashedIncoming, "contents are identical")),
+    };
+
+    match outcome {
+        Some((action, reason)) => {
+            if action == CollisionAction::ReplacedExisting {
+                reserved.insert(candidate.clone());
+            }
+            Destination::Move(
+                candidate,
+                Some(CollisionDecision {
+                    policy,
+                    action,
+                    reason: reason.to_string(),
+                }),
+            )
+        }
+        None => rename_destination(candidate, ext_key, policy, "destination exists", reserved),
+    }
+}
+
+fn rename_destination(
+    candidate: PathBuf,
+    ext_key: &str,
+    policy: CollisionPolicy,
+    reason: &str,
+    reserved: &mut HashSet<PathBuf>,
+) -> Destination {
     let parent = candidate
         .parent()
         .map(|x| x.to_path_buf())
         .unwrap_or_else(|| PathBuf::from("."));
-    let stem = candidate
-        .file_stem()
-        .map(|x| x.to_string_lossy().to_string())
-        .unwrap_or_else(|| "file".to_string());
-    let ext = candidate
-        .extension()
-        .map(|x| x.to_string_lossy().to_string())
-        .unwrap_or_default();
+    let (stem, ext) = split_file_name(&candidate, ext_key);
 
     let mut idx = 1;
     loop {
@@ -221,12 +1434,43 @@ fn resolve_destination(candidate: PathBuf, reserved: &mut HashSet<PathBuf>) -> (
         let next = parent.join(file_name);
         if !next.exists() && !reserved.contains(&next) {
             reserved.insert(next.clone());
-            return (next, true);
+            return Destination::Move(
+                next,
+                Some(CollisionDecision {
+                    policy,
+                    action: CollisionAction::Renamed,
+                    reason: reason.to_string(),
+                }),
+            );
         }
         idx += 1;
     }
 }
 
+fn is_renamed(collision: &Option<CollisionDecision>) -> bool {
+    collision
+        .as_ref()
+        .is_some_and(|decision| decision.action == CollisionAction::Renamed)
+}
+
+/// Splits a file name into stem and extension, keeping a compound `ext_key` such as
+/// `tar.gz` together so renames produce `backup (1).tar.gz`.
+fn split_file_name(path: &Path, ext_key: &str) -> (String, String) {
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+
+    if !ext_key.is_empty() && name.len() > ext_key.len() + 1 {
+        let dot = name.len() - ext_key.len() - 1;
+        if name.is_char_boundary(dot) && name[dot..].starts_with('.') {
+            return (name[..dot].to_string(), name[dot + 1..].to_string());
+        }
+    }
+
+    (name, String::new())
+}
+
 fn is_old_enough(path: &Path, min_age_seconds: u64) -> bool {
     let Ok(metadata) = fs::metadata(path) else {
         return false;
@@ -240,6 +1484,68 @@ fn is_old_enough(path: &Path, min_age_seconds: u64) -> bool {
     age.as_secs() >= min_age_seconds
 }
 
+/// The part of a dropped tree kept under the category folder for the given sort mode.
+/// Files dropped directly into the sort root have no tree, so every mode flattens them.
+fn drop_subpath(path: &Path, root: &Path, mode: SortMode) -> PathBuf {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return PathBuf::new();
+    };
+    let parts: Vec<_> = relative.iter().collect();
+    if parts.len() < 2 {
+        return PathBuf::new();
+    }
+
+    match mode {
+        SortMode::Flatten => PathBuf::new(),
+        SortMode::Preserve => parts[1..parts.len() - 1].iter().collect(),
+        SortMode::GroupByDrop => PathBuf::from(parts[0]),
+    }
+}
+
+fn in_progress_match<'a>(file_name: &str, patterns: &'a [(String, glob::Pattern)]) -> Option<&'a str> {
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    patterns
+        .iter()
+        .find(|(_, pattern)| pattern.matches_with(file_name, options))
+        .map(|(raw, _)| raw.as_str())
+}
+
+/// The process writing `path`, or for a bundle, writing anything inside it.
+fn open_holder<'a>(path: &Path, is_bundle: bool, open_files: &'a HashMap<PathBuf, String>) -> Option<&'a str> {
+    if open_files.is_empty() {
+        return None;
+    }
+    // Open files are keyed by canonical path; the walker's paths keep the root's symlinks.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.as_path();
+    if is_bundle {
+        open_files
+            .iter()
+            .find(|(open, _)| open.starts_with(path))
+            .map(|(_, holder)| holder.as_str())
+    } else {
+        open_files.get(path).map(String::as_str)
+    }
+}
+
+fn sample_size(path: &Path) -> io::Result<(u64, SystemTime)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified()?))
+}
+
+/// A bundle is only movable once nothing inside it is still being written.
+fn is_tree_old_enough(dir: &Path, min_age_seconds: u64) -> bool {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .all(|entry| is_old_enough(entry.path(), min_age_seconds))
+}
+
 fn is_inside_protected(path: &Path, root: &Path, protected: &HashSet<String>) -> bool {
     let Ok(relative) = path.strip_prefix(root) else {
         return false;
@@ -1,15 +1,26 @@
 use crate::errors::{AppError, AppResult};
+use crate::exclude;
+use crate::migrations;
+use crate::template;
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Rules {
+    /// On-disk format version; see `migrations`. Absent in files from the first release.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub global: GlobalRules,
     pub categories: Vec<CategoryRule>,
     pub misc: MiscRule,
+    #[serde(default)]
+    pub bundles: Vec<BundleRule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,12 +33,117 @@ pub struct GlobalRules {
     pub no_extension_goes_to_misc: bool,
     pub min_file_age_seconds: u64,
     pub cleanup_empty_folders: CleanupRules,
+    #[serde(default)]
+    pub content_sniffing: ContentSniffing,
+    #[serde(default)]
+    pub sort_mode: SortMode,
+    /// Gitignore-style patterns that are never sorted, relative to the sort root or to the
+    /// inbox folder being walked.
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// Filename globs for downloads and lock files that are still being written.
+    #[serde(default = "default_in_progress_patterns")]
+    pub in_progress_patterns: Vec<String>,
+    /// Only move a file once its size and mtime are unchanged across two planner passes.
+    #[serde(default = "default_true")]
+    pub require_stable_size: bool,
+    /// Skip files another process holds open for writing (Linux `/proc` scan).
+    #[serde(default = "default_true")]
+    pub skip_open_files: bool,
+    /// Folders outside the sort root whose files are sorted into it, e.g. `~/Downloads`.
+    #[serde(default)]
+    pub inbox_folders: Vec<InboxFolder>,
+    #[serde(default)]
+    pub duplicate_detection: DuplicateDetection,
 }
 
+/// Content-hash duplicate detection while planning. A candidate is a duplicate when it is
+/// byte-identical to the existing file at its destination or to an earlier candidate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateDetection {
+    pub enabled: bool,
+    #[serde(default)]
+    pub action: DuplicateAction,
+}
+
+/// What happens to a detected duplicate.
+///
+/// - `keep`: sort it as usual; the plan only marks it.
+/// - `trash`: move it to the Trash.
+/// - `moveToDuplicates`: move it to `<sort root>/Duplicates`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateAction {
+    #[default]
+    Keep,
+    Trash,
+    MoveToDuplicates,
+}
+
+/// Top-level folder under the sort root that receives `moveToDuplicates` files. It is never
+/// sorted or cleaned up.
+pub const DUPLICATES_FOLDER: &str = "Duplicates";
+
+/// An external folder feeding the sort root. Only its top-level entries are sorted unless
+/// `recursive` is set; folders left empty in an inbox are never cleaned up.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub struct InboxFolder {
+    pub path: String,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+/// How much of a dropped folder tree survives under the category folder.
+///
+/// - `flatten`: `Category/file`
+/// - `preserve`: `Category/<path below the dropped folder>/file`
+/// - `groupByDrop`: `Category/<dropped folder name>/file`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortMode {
+    #[default]
+    Flatten,
+    Preserve,
+    GroupByDrop,
+}
+
+/// What to do when a destination already exists on disk.
+///
+/// - `rename`: move to `name (1).ext`, `name (2).ext`, ...
+/// - `skip`: leave the incoming file where it is.
+/// - `overwriteToTrash`: trash the existing file, then move.
+/// - `keepNewer` / `keepLarger`: keep whichever file has the later mtime / larger size and
+///   trash the other.
+/// - `dedupeIfIdentical`: trash the incoming file when its contents match the existing one,
+///   otherwise rename.
+///
+/// Two files of the same plan claiming one name are always renamed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum CollisionPolicy {
+    #[default]
     Rename,
+    Skip,
+    OverwriteToTrash,
+    KeepNewer,
+    KeepLarger,
+    DedupeIfIdentical,
+}
+
+/// When to read a file's leading bytes to identify its type.
+///
+/// - `off`: classify by name only.
+/// - `fallback`: sniff files with no extension or an unknown one.
+/// - `override`: sniff every file and let a recognised signature beat a mismatched extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentSniffing {
+    #[default]
+    Off,
+    Fallback,
+    Override,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +167,10 @@ pub struct CategoryRule {
     pub name: String,
     pub target_subfolder: String,
     pub extensions: Vec<String>,
+    #[serde(default)]
+    pub filename_globs: Vec<String>,
+    #[serde(default)]
+    pub filename_regexes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +180,23 @@ pub struct MiscRule {
     pub target_subfolder: String,
 }
 
+/// A directory that is moved intact instead of being sorted file by file.
+///
+/// A directory matches when it directly contains any of `markerEntries` (e.g. `.git`,
+/// `Cargo.toml`) or its name ends in one of `dirSuffixes` (e.g. `app`). `categoryId`
+/// names the category whose `targetSubfolder` receives the bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleRule {
+    pub id: String,
+    pub name: String,
+    pub category_id: String,
+    #[serde(default)]
+    pub marker_entries: Vec<String>,
+    #[serde(default)]
+    pub dir_suffixes: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ValidationResult {
@@ -68,6 +205,37 @@ pub struct ValidationResult {
     pub warnings: Vec<String>,
 }
 
+/// Compiled filename patterns for one category.
+///
+/// Filename patterns are checked before extensions: the first category (in rule order)
+/// with a matching glob or regex wins, globs are tried before regexes within a category,
+/// and only files no pattern claims fall through to the extension lookup.
+#[derive(Debug, Clone)]
+pub struct FilenameMatcher {
+    pub category_id: String,
+    globs: Vec<(String, glob::Pattern)>,
+    regexes: Vec<(String, Regex)>,
+    glob_options: glob::MatchOptions,
+}
+
+impl FilenameMatcher {
+    /// Every pattern that matches `file_name`, globs before regexes, tagged `filenameGlob`
+    /// or `filenameRegex`.
+    pub fn matching_patterns(&self, file_name: &str) -> Vec<(&'static str, &str)> {
+        let globs = self
+            .globs
+            .iter()
+            .filter(|(_, pattern)| pattern.matches_with(file_name, self.glob_options))
+            .map(|(raw, _)| ("filenameGlob", raw.as_str()));
+        let regexes = self
+            .regexes
+            .iter()
+            .filter(|(_, regex)| regex.is_match(file_name))
+            .map(|(raw, _)| ("filenameRegex", raw.as_str()));
+        globs.chain(regexes).collect()
+    }
+}
+
 pub fn suggested_sort_root() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -89,8 +257,100 @@ pub fn journal_path() -> AppResult<PathBuf> {
     Ok(config_dir()?.join("journal.jsonl"))
 }
 
+/// Profile backed by the top-level `rules.json` / `journal.jsonl`; it always exists.
+pub const DEFAULT_PROFILE_ID: &str = "default";
+
+/// Extra profiles live in `profiles/<id>/` next to the default profile's files.
+pub fn profiles_dir() -> AppResult<PathBuf> {
+    Ok(config_dir()?.join("profiles"))
+}
+
+/// Rules and journal paths for a profile.
+pub fn profile_paths(id: &str) -> AppResult<(PathBuf, PathBuf)> {
+    if id == DEFAULT_PROFILE_ID {
+        return Ok((rules_path()?, journal_path()?));
+    }
+    let dir = profiles_dir()?.join(id);
+    Ok((dir.join("rules.json"), dir.join("journal.jsonl")))
+}
+
+/// The default profile followed by every `profiles/<id>/` that has a `rules.json`, sorted by id.
+pub fn list_profile_ids() -> AppResult<Vec<String>> {
+    let mut ids = Vec::new();
+    let dir = profiles_dir()?;
+    if dir.is_dir() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let id = entry.file_name().to_string_lossy().to_string();
+            if validate_profile_id(&id).is_ok() && entry.path().join("rules.json").is_file() {
+                ids.push(id);
+            }
+        }
+    }
+    ids.sort();
+    ids.insert(0, DEFAULT_PROFILE_ID.to_string());
+    Ok(ids)
+}
+
+/// Profile ids double as folder names, so keep them short and portable.
+pub fn validate_profile_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > 64 {
+        return Err("profile id must be 1-64 characters".to_string());
+    }
+    if id == DEFAULT_PROFILE_ID {
+        return Err(format!("'{}' is reserved", DEFAULT_PROFILE_ID));
+    }
+    if !id
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    {
+        return Err(format!(
+            "profile id '{}' may only contain letters, digits, '-' and '_'",
+            id
+        ));
+    }
+    Ok(())
+}
+
+/// True when one sort root contains the other; two profiles may not sort the same tree.
+pub fn sort_roots_overlap(left: &str, right: &str) -> bool {
+    let left = Path::new(left.trim());
+    let right = Path::new(right.trim());
+    left.starts_with(right) || right.starts_with(left)
+}
+
+/// Temporary names used by browsers, torrent clients and office suites while a file is
+/// incomplete or open.
+pub fn default_in_progress_patterns() -> Vec<String> {
+    [
+        "*.crdownload",
+        "*.part",
+        "*.partial",
+        "*.download",
+        "*.opdownload",
+        "*.!qB",
+        "*.!ut",
+        "~$*",
+        ".~lock.*#",
+    ]
+    .iter()
+    .map(|x| x.to_string())
+    .collect()
+}
+
+fn current_schema_version() -> u32 {
+    migrations::CURRENT_SCHEMA_VERSION
+}
+
+/// Serde default for gates that `default_rules` turns on; `migrations` writes `false`
+/// explicitly for files from before they existed.
+fn default_true() -> bool {
+    true
+}
+
 pub fn default_rules() -> Rules {
     Rules {
+        schema_version: migrations::CURRENT_SCHEMA_VERSION,
         global: GlobalRules {
             sort_root: suggested_sort_root().to_string_lossy().to_string(),
             case_insensitive_ext: true,
@@ -103,6 +363,14 @@ pub fn default_rules() -> Rules {
                 min_age_seconds: 60,
                 mode: CleanupMode::Trash,
             },
+            content_sniffing: ContentSniffing::Off,
+            sort_mode: SortMode::Flatten,
+            exclude_patterns: Vec::new(),
+            in_progress_patterns: default_in_progress_patterns(),
+            require_stable_size: true,
+            skip_open_files: true,
+            inbox_folders: Vec::new(),
+            duplicate_detection: DuplicateDetection::default(),
         },
         categories: vec![
             CategoryRule {
@@ -116,6 +384,8 @@ pub fn default_rules() -> Rules {
                 .iter()
                 .map(|x| x.to_string())
                 .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "images".to_string(),
@@ -125,6 +395,8 @@ pub fn default_rules() -> Rules {
                     .iter()
                     .map(|x| x.to_string())
                     .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "video".to_string(),
@@ -134,6 +406,8 @@ pub fn default_rules() -> Rules {
                     .iter()
                     .map(|x| x.to_string())
                     .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "audio".to_string(),
@@ -143,15 +417,21 @@ pub fn default_rules() -> Rules {
                     .iter()
                     .map(|x| x.to_string())
                     .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "archives".to_string(),
                 name: "Archives".to_string(),
                 target_subfolder: "Archives".to_string(),
-                extensions: ["zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "iso"]
-                    .iter()
-                    .map(|x| x.to_string())
-                    .collect(),
+                extensions: [
+                    "zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "iso", "tar.gz", "tar.bz2", "tar.xz", "tar.zst",
+                ]
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "code".to_string(),
@@ -164,15 +444,19 @@ pub fn default_rules() -> Rules {
                 .iter()
                 .map(|x| x.to_string())
                 .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "executables".to_string(),
                 name: "Executables".to_string(),
                 target_subfolder: "Executables".to_string(),
-                extensions: ["exe", "msi", "deb", "rpm", "app", "apk", "jar"]
+                extensions: ["exe", "msi", "deb", "rpm", "app", "apk", "jar", "elf"]
                     .iter()
                     .map(|x| x.to_string())
                     .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
             CategoryRule {
                 id: "data".to_string(),
@@ -182,22 +466,49 @@ pub fn default_rules() -> Rules {
                     .iter()
                     .map(|x| x.to_string())
                     .collect(),
+                filename_globs: Vec::new(),
+                filename_regexes: Vec::new(),
             },
         ],
         misc: MiscRule {
             name: "Misc".to_string(),
             target_subfolder: "Misc".to_string(),
         },
+        bundles: vec![
+            BundleRule {
+                id: "projects".to_string(),
+                name: "Projects".to_string(),
+                category_id: "code".to_string(),
+                marker_entries: [".git", "Cargo.toml", "package.json"]
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect(),
+                dir_suffixes: Vec::new(),
+            },
+            BundleRule {
+                id: "app-bundles".to_string(),
+                name: "App bundles".to_string(),
+                category_id: "executables".to_string(),
+                marker_entries: Vec::new(),
+                dir_suffixes: vec!["app".to_string()],
+            },
+            BundleRule {
+                id: "photo-libraries".to_string(),
+                name: "Photo libraries".to_string(),
+                category_id: "images".to_string(),
+                marker_entries: Vec::new(),
+                dir_suffixes: vec!["photoslibrary".to_string()],
+            },
+        ],
     }
 }
 
 pub fn load_or_create_rules(path: &Path) -> AppResult<Rules> {
     if path.exists() {
-        let content = fs::read_to_string(path)?;
-        let parsed: Rules = serde_json::from_str(&content)?;
-        let validation = validate_rules(&parsed);
-        if !validation.valid {
-            return Err(AppError::Validation(validation.errors.join("; ")));
+        let (parsed, found) = parse_rules(&fs::read_to_string(path)?)?;
+        if found < migrations::CURRENT_SCHEMA_VERSION {
+            backup_rules_file(path, found)?;
+            save_rules(path, &parsed)?;
         }
         Ok(parsed)
     } else {
@@ -207,15 +518,178 @@ pub fn load_or_create_rules(path: &Path) -> AppResult<Rules> {
     }
 }
 
-pub fn save_rules(path: &Path, rules: &Rules) -> AppResult<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+/// Parses, migrates and validates a `rules.json` document. Also returns the schema version
+/// the document was written with.
+fn parse_rules(content: &str) -> AppResult<(Rules, u32)> {
+    let mut document: serde_json::Value = serde_json::from_str(content)?;
+    let found = migrations::migrate(&mut document)?;
+    let parsed: Rules = serde_json::from_value(document)?;
+    let validation = validate_rules(&parsed);
+    if !validation.valid {
+        return Err(AppError::Validation(validation.errors.join("; ")));
     }
+    Ok((parsed, found))
+}
+
+/// Copies `rules.json` to `rules.json.v<version>.<timestamp>.bak` before a migration rewrites it.
+fn backup_rules_file(path: &Path, version: u32) -> AppResult<PathBuf> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "rules.json".to_string());
+    let backup = path.with_file_name(format!(
+        "{}.v{}.{}.bak",
+        file_name,
+        version,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Writes `rules` atomically: the previous file is kept in the history folder, the new
+/// contents go to a temp file that is fsynced and then renamed over `path`, so a crash
+/// leaves either the old or the new file, never a truncated one.
+pub fn save_rules(path: &Path, rules: &Rules) -> AppResult<()> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| AppError::State(format!("'{}' has no parent folder", path.display())))?;
+    fs::create_dir_all(parent)?;
+
     let payload = serde_json::to_string_pretty(rules)?;
-    fs::write(path, payload)?;
+    if path.is_file() {
+        snapshot_rules_file(path, payload.as_bytes())?;
+    }
+
+    let temp_path = path.with_extension("json.tmp");
+    {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(payload.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)?;
+    sync_dir(parent);
+    Ok(())
+}
+
+/// Makes the rename durable. Directories cannot be opened for syncing on Windows, where
+/// the rename is already journaled by the filesystem.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(handle) = fs::File::open(dir) {
+        let _ = handle.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
+/// Previous `rules.json` versions kept per profile.
+pub const RULES_HISTORY_LIMIT: usize = 10;
+
+const RULES_HISTORY_DIR: &str = "rules-history";
+
+/// A saved copy of an earlier `rules.json`. `id` is its file name in the history folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RulesVersion {
+    pub id: String,
+    pub saved_at: String,
+    /// `None` when the copy no longer parses (e.g. it was written by a newer app).
+    pub sort_root: Option<String>,
+}
+
+fn rules_history_dir(rules_path: &Path) -> PathBuf {
+    rules_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(RULES_HISTORY_DIR)
+}
+
+/// Copies the current `rules.json` into the history folder before it is replaced by
+/// `next`, then drops copies beyond `RULES_HISTORY_LIMIT`. Re-saving identical contents
+/// keeps no copy.
+fn snapshot_rules_file(path: &Path, next: &[u8]) -> AppResult<()> {
+    let current = fs::read(path)?;
+    if current == next {
+        return Ok(());
+    }
+
+    let history = rules_history_dir(path);
+    fs::create_dir_all(&history)?;
+    let mut ids = history_ids(&history)?;
+
+    // Ids must sort after the newest copy even when saves land in the same microsecond.
+    let mut stamp = Utc::now();
+    let mut id = history_id(stamp);
+    while ids.last().is_some_and(|newest| *newest >= id) {
+        stamp += chrono::Duration::microseconds(1);
+        id = history_id(stamp);
+    }
+    fs::write(history.join(&id), &current)?;
+    ids.push(id);
+
+    let excess = ids.len().saturating_sub(RULES_HISTORY_LIMIT);
+    for id in ids.iter().take(excess) {
+        let _ = fs::remove_file(history.join(id));
+    }
     Ok(())
 }
 
+fn history_id(stamp: DateTime<Utc>) -> String {
+    format!("rules-{}.json", stamp.format("%Y%m%dT%H%M%S%.6fZ"))
+}
+
+/// History file names, oldest first. The timestamp in the name sorts chronologically.
+fn history_ids(history: &Path) -> AppResult<Vec<String>> {
+    let mut ids = Vec::new();
+    if !history.is_dir() {
+        return Ok(ids);
+    }
+    for entry in fs::read_dir(history)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name.starts_with("rules-") && name.ends_with(".json") {
+            ids.push(name);
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Saved versions of the rules at `rules_path`, newest first.
+pub fn list_rules_versions(rules_path: &Path) -> AppResult<Vec<RulesVersion>> {
+    let history = rules_history_dir(rules_path);
+    let mut versions = Vec::new();
+
+    for id in history_ids(&history)?.into_iter().rev() {
+        let path = history.join(&id);
+        let saved_at = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .map(|time| DateTime::<Utc>::from(time).to_rfc3339())
+            .unwrap_or_default();
+        let sort_root = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| parse_rules(&content).ok())
+            .map(|(rules, _)| rules.global.sort_root);
+        versions.push(RulesVersion {
+            id,
+            saved_at,
+            sort_root,
+        });
+    }
+    Ok(versions)
+}
+
+/// Loads one saved version, migrated and validated. The caller decides whether to save it.
+pub fn load_rules_version(rules_path: &Path, id: &str) -> AppResult<Rules> {
+    let history = rules_history_dir(rules_path);
+    if !history_ids(&history)?.iter().any(|known| known == id) {
+        return Err(AppError::Validation(format!("unknown rules version '{}'", id)));
+    }
+    let (rules, _) = parse_rules(&fs::read_to_string(history.join(id))?)?;
+    Ok(rules)
+}
+
 pub fn validate_rules(rules: &Rules) -> ValidationResult {
     let mut errors = Vec::new();
     let mut warnings = Vec::new();
@@ -224,15 +698,65 @@ pub fn validate_rules(rules: &Rules) -> ValidationResult {
         errors.push("sortRoot cannot be empty".to_string());
     }
 
+    if rules.schema_version > migrations::CURRENT_SCHEMA_VERSION {
+        errors.push(format!(
+            "schemaVersion {} is newer than this app supports ({})",
+            rules.schema_version,
+            migrations::CURRENT_SCHEMA_VERSION
+        ));
+    }
+
     if rules.categories.is_empty() {
         errors.push("at least one category is required".to_string());
     }
 
+    for pattern in &rules.global.in_progress_patterns {
+        if let Err(err) = glob::Pattern::new(pattern) {
+            errors.push(format!("invalid inProgressPatterns entry '{}': {}", pattern, err));
+        }
+    }
+
+    for pattern in &rules.global.exclude_patterns {
+        if let Err(err) = exclude::validate_pattern(pattern) {
+            errors.push(format!("invalid exclude pattern '{}': {}", pattern, err));
+        }
+    }
+
+    let mut seen_inboxes = HashSet::new();
+    for inbox in &rules.global.inbox_folders {
+        let path = inbox.path.trim();
+        if path.is_empty() {
+            errors.push("inboxFolders entry has an empty path".to_string());
+            continue;
+        }
+        if !Path::new(path).is_absolute() {
+            errors.push(format!("inbox folder '{}' must be an absolute path", path));
+        }
+        if sort_roots_overlap(path, &rules.global.sort_root) {
+            errors.push(format!("inbox folder '{}' overlaps the sort root", path));
+        }
+        if !seen_inboxes.insert(path.to_string()) {
+            warnings.push(format!("inbox folder '{}' is listed more than once", path));
+        } else if !Path::new(path).is_dir() {
+            warnings.push(format!("inbox folder '{}' does not exist yet", path));
+        }
+    }
+
+    if rules.misc.target_subfolder.trim().is_empty() {
+        errors.push("misc has empty targetSubfolder".to_string());
+    }
+    for err in template::validate(&rules.misc.target_subfolder) {
+        errors.push(format!("misc targetSubfolder {}", err));
+    }
+
     let mut seen_ext = HashMap::new();
     for category in &rules.categories {
         if category.target_subfolder.trim().is_empty() {
             errors.push(format!("category '{}' has empty targetSubfolder", category.name));
         }
+        for err in template::validate(&category.target_subfolder) {
+            errors.push(format!("category '{}' targetSubfolder {}", category.name, err));
+        }
 
         for ext in &category.extensions {
             let norm = normalize_extension(ext, rules.global.case_insensitive_ext);
@@ -247,6 +771,53 @@ pub fn validate_rules(rules: &Rules) -> ValidationResult {
                 ));
             }
         }
+
+        for raw in &category.filename_globs {
+            if raw.trim().is_empty() {
+                warnings.push(format!("category '{}' includes empty filename glob", category.name));
+                continue;
+            }
+            if let Err(err) = glob::Pattern::new(raw) {
+                errors.push(format!(
+                    "category '{}' has invalid filename glob '{}': {}",
+                    category.name, raw, err
+                ));
+            }
+        }
+
+        for raw in &category.filename_regexes {
+            if raw.trim().is_empty() {
+                warnings.push(format!("category '{}' includes empty filename regex", category.name));
+                continue;
+            }
+            if let Err(err) = Regex::new(raw) {
+                errors.push(format!(
+                    "category '{}' has invalid filename regex '{}': {}",
+                    category.name, raw, err
+                ));
+            }
+        }
+    }
+
+    for bundle in &rules.bundles {
+        if !rules.categories.iter().any(|category| category.id == bundle.category_id) {
+            errors.push(format!(
+                "bundle '{}' references unknown categoryId '{}'",
+                bundle.name, bundle.category_id
+            ));
+        }
+        if bundle.marker_entries.is_empty() && bundle.dir_suffixes.is_empty() {
+            warnings.push(format!("bundle '{}' has no markers or suffixes and never matches", bundle.name));
+        }
+        for marker in &bundle.marker_entries {
+            let marker = marker.trim();
+            if marker.is_empty() || marker == "." || marker == ".." || marker.contains(['/', '\\']) {
+                errors.push(format!(
+                    "bundle '{}' marker '{}' must be a plain file or folder name",
+                    bundle.name, marker
+                ));
+            }
+        }
     }
 
     ValidationResult {
@@ -265,6 +836,17 @@ pub fn normalize_extension(ext: &str, case_insensitive: bool) -> String {
     }
 }
 
+/// Every dot-separated suffix of `file_name`, longest first, so compound extensions
+/// such as `tar.gz` can win over `gz`. A leading dot (dotfiles) does not start an extension.
+pub fn extension_candidates(file_name: &str) -> Vec<&str> {
+    file_name
+        .char_indices()
+        .filter(|(idx, ch)| *ch == '.' && *idx > 0)
+        .map(|(idx, _)| &file_name[idx + 1..])
+        .filter(|suffix| !suffix.is_empty())
+        .collect()
+}
+
 pub fn extension_lookup(rules: &Rules) -> HashMap<String, String> {
     let mut map = HashMap::new();
     for category in &rules.categories {
@@ -280,12 +862,109 @@ pub fn extension_lookup(rules: &Rules) -> HashMap<String, String> {
     map
 }
 
+/// Compiles filename globs and regexes per category, preserving rule order.
+/// Empty or invalid patterns are dropped; `validate_rules` reports them.
+pub fn filename_matchers(rules: &Rules) -> Vec<FilenameMatcher> {
+    let case_insensitive = rules.global.case_insensitive_ext;
+    let glob_options = glob::MatchOptions {
+        case_sensitive: !case_insensitive,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+
+    rules
+        .categories
+        .iter()
+        .filter_map(|category| {
+            let globs: Vec<_> = category
+                .filename_globs
+                .iter()
+                .filter(|raw| !raw.trim().is_empty())
+                .filter_map(|raw| glob::Pattern::new(raw).ok().map(|pattern| (raw.clone(), pattern)))
+                .collect();
+            let regexes: Vec<_> = category
+                .filename_regexes
+                .iter()
+                .filter(|raw| !raw.trim().is_empty())
+                .filter_map(|raw| {
+                    RegexBuilder::new(raw)
+                        .case_insensitive(case_insensitive)
+                        .build()
+                        .ok()
+                        .map(|regex| (raw.clone(), regex))
+                })
+                .collect();
+
+            if globs.is_empty() && regexes.is_empty() {
+                return None;
+            }
+
+            Some(FilenameMatcher {
+                category_id: category.id.clone(),
+                globs,
+                regexes,
+                glob_options,
+            })
+        })
+        .collect()
+}
+
+/// Returns the first bundle rule matching directory `dir`, with the receiving category's
+/// `targetSubfolder`. Rules are checked in order; suffixes are compared like extensions.
+pub fn match_bundle<'a>(rules: &'a Rules, dir: &Path) -> Option<(&'a BundleRule, &'a str)> {
+    let dir_name = dir.file_name()?.to_string_lossy().to_string();
+    let case_insensitive = rules.global.case_insensitive_ext;
+    let suffixes: Vec<String> = extension_candidates(&dir_name)
+        .into_iter()
+        .map(|ext| normalize_extension(ext, case_insensitive))
+        .collect();
+
+    rules.bundles.iter().find_map(|bundle| {
+        let by_suffix = bundle
+            .dir_suffixes
+            .iter()
+            .map(|suffix| normalize_extension(suffix, case_insensitive))
+            .any(|suffix| !suffix.is_empty() && suffixes.contains(&suffix));
+        let by_marker = || {
+            bundle
+                .marker_entries
+                .iter()
+                .map(|marker| marker.trim())
+                .filter(|marker| !marker.is_empty())
+                .any(|marker| dir.join(marker).exists())
+        };
+        if !by_suffix && !by_marker() {
+            return None;
+        }
+
+        rules
+            .categories
+            .iter()
+            .find(|category| category.id == bundle.category_id)
+            .map(|category| (bundle, category.target_subfolder.as_str()))
+    })
+}
+
+/// Compiles `inProgressPatterns`. Matching ignores case because clients vary (`.!qB`, `.PART`).
+pub fn in_progress_matchers(rules: &Rules) -> Vec<(String, glob::Pattern)> {
+    rules
+        .global
+        .in_progress_patterns
+        .iter()
+        .filter(|raw| !raw.trim().is_empty())
+        .filter_map(|raw| glob::Pattern::new(raw).ok().map(|pattern| (raw.clone(), pattern)))
+        .collect()
+}
+
+/// Top-level folders owned by categories. A templated `targetSubfolder` such as
+/// `Images/{mtime:%Y}` protects only its static first component, `Images`.
 pub fn protected_top_level_folders(rules: &Rules) -> HashSet<String> {
     let mut set = HashSet::new();
     for category in &rules.categories {
-        set.insert(category.target_subfolder.clone());
+        set.insert(template::top_level_folder(&category.target_subfolder));
     }
-    set.insert(rules.misc.target_subfolder.clone());
+    set.insert(template::top_level_folder(&rules.misc.target_subfolder));
+    set.remove("");
     set
 }
 
@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A file type recognised from its leading bytes.
+///
+/// `extensions` lists every extension that legitimately carries this signature, in routing
+/// preference order. A `.docx` is a ZIP container, so it must not be "corrected" to `.zip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub label: &'static str,
+    pub extensions: &'static [&'static str],
+}
+
+/// Covers every fixed-offset signature and the DOS header's `e_lfanew` field at 0x3C.
+const HEADER_LEN: usize = 64;
+
+/// Largest `e_lfanew` followed when looking for a PE header.
+const MAX_PE_OFFSET: usize = 4096;
+
+const PDF: Signature = Signature {
+    label: "pdf",
+    extensions: &["pdf"],
+};
+const PNG: Signature = Signature {
+    label: "png",
+    extensions: &["png"],
+};
+const JPEG: Signature = Signature {
+    label: "jpeg",
+    extensions: &["jpg", "jpeg", "jpe", "jfif"],
+};
+const GIF: Signature = Signature {
+    label: "gif",
+    extensions: &["gif"],
+};
+const WEBP: Signature = Signature {
+    label: "webp",
+    extensions: &["webp"],
+};
+const ZIP: Signature = Signature {
+    label: "zip",
+    extensions: &[
+        "zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "jar", "apk", "xpi", "whl", "nupkg",
+    ],
+};
+const RAR: Signature = Signature {
+    label: "rar",
+    extensions: &["rar"],
+};
+const SEVEN_ZIP: Signature = Signature {
+    label: "7z",
+    extensions: &["7z"],
+};
+const GZIP: Signature = Signature {
+    label: "gzip",
+    extensions: &["gz", "tgz"],
+};
+const BZIP2: Signature = Signature {
+    label: "bzip2",
+    extensions: &["bz2", "tbz2"],
+};
+const ELF: Signature = Signature {
+    label: "elf",
+    extensions: &["elf", "so", "o"],
+};
+const PE: Signature = Signature {
+    label: "pe",
+    extensions: &["exe", "dll", "sys", "scr"],
+};
+const MP4: Signature = Signature {
+    label: "mp4",
+    extensions: &["mp4", "m4v", "m4a", "mov", "3gp", "heic", "heif", "avif"],
+};
+const MATROSKA: Signature = Signature {
+    label: "matroska",
+    extensions: &["mkv", "webm", "mka"],
+};
+const WAV: Signature = Signature {
+    label: "wav",
+    extensions: &["wav"],
+};
+const AVI: Signature = Signature {
+    label: "avi",
+    extensions: &["avi"],
+};
+const MP3: Signature = Signature {
+    label: "mp3",
+    extensions: &["mp3"],
+};
+const FLAC: Signature = Signature {
+    label: "flac",
+    extensions: &["flac"],
+};
+const OGG: Signature = Signature {
+    label: "ogg",
+    extensions: &["ogg", "oga", "ogv", "opus"],
+};
+const SQLITE: Signature = Signature {
+    label: "sqlite",
+    extensions: &["sqlite", "db", "sqlite3"],
+};
+
+/// Reads the first bytes of `path` and matches them against known signatures.
+pub fn detect(path: &Path) -> Option<Signature> {
+    let mut file = File::open(path).ok()?;
+    let mut header = vec![0_u8; HEADER_LEN];
+    let read = read_up_to(&mut file, &mut header)?;
+    header.truncate(read);
+
+    // The PE header usually sits past `HEADER_LEN`; read up to it when the DOS header
+    // points somewhere plausible.
+    if let Some(offset) = pe_header_offset(&header) {
+        if offset <= MAX_PE_OFFSET && offset + 4 > header.len() {
+            let mut magic = [0_u8; 4];
+            file.seek(SeekFrom::Start(offset as u64)).ok()?;
+            let read = read_up_to(&mut file, &mut magic)?;
+            header.resize(offset, 0);
+            header.extend_from_slice(&magic[..read]);
+        }
+    }
+    detect_bytes(&header)
+}
+
+fn read_up_to(file: &mut File, buffer: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(count) => read += count,
+            Err(_) => return None,
+        }
+    }
+    Some(read)
+}
+
+/// `e_lfanew` of an `MZ` header: where the `PE\0\0` signature should be.
+fn pe_header_offset(header: &[u8]) -> Option<usize> {
+    if !header.starts_with(b"MZ") {
+        return None;
+    }
+    let field: [u8; 4] = header.get(0x3C..0x40)?.try_into().ok()?;
+    Some(u32::from_le_bytes(field) as usize)
+}
+
+fn is_pe(header: &[u8]) -> bool {
+    pe_header_offset(header).is_some_and(|offset| {
+        header.get(offset..offset.saturating_add(4)) == Some(b"PE\0\0".as_slice())
+    })
+}
+
+/// An MPEG audio Layer III frame header: 11-bit frame sync, then a version, bitrate,
+/// sample rate and emphasis that are not reserved or invalid.
+fn is_mp3_frame(header: &[u8]) -> bool {
+    let Some(&[first, second, third, fourth]) = header.get(..4) else {
+        return false;
+    };
+    let version = (second >> 3) & 0b11;
+    let layer = (second >> 1) & 0b11;
+    let bitrate = third >> 4;
+    let sample_rate = (third >> 2) & 0b11;
+    let emphasis = fourth & 0b11;
+
+    first == 0xFF
+        && second & 0xE0 == 0xE0
+        && version != 0b01
+        && layer == 0b01
+        && bitrate != 0b0000
+        && bitrate != 0b1111
+        && sample_rate != 0b11
+        && emphasis != 0b10
+}
+
+pub fn detect_bytes(header: &[u8]) -> Option<Signature> {
+    let riff_kind = |kind: &[u8]| header.starts_with(b"RIFF") && header.get(8..12) == Some(kind);
+
+    let signature = if header.starts_with(b"%PDF-") {
+        PDF
+    } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        PNG
+    } else if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        JPEG
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        GIF
+    } else if riff_kind(b"WEBP") {
+        WEBP
+    } else if riff_kind(b"WAVE") {
+        WAV
+    } else if riff_kind(b"AVI ") {
+        AVI
+    } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        ZIP
+    } else if header.starts_with(b"Rar!\x1a\x07") {
+        RAR
+    } else if header.starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        SEVEN_ZIP
+    } else if header.starts_with(&[0x1F, 0x8B]) {
+        GZIP
+    } else if header.starts_with(b"BZh") {
+        BZIP2
+    } else if header.starts_with(b"\x7fELF") {
+        ELF
+    } else if is_pe(header) {
+        PE
+    } else if header.get(4..8) == Some(b"ftyp") {
+        MP4
+    } else if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        MATROSKA
+    } else if header.starts_with(b"ID3") || is_mp3_frame(header) {
+        MP3
+    } else if header.starts_with(b"fLaC") {
+        FLAC
+    } else if header.starts_with(b"OggS") {
+        OGG
+    } else if header.starts_with(b"SQLite format 3\0") {
+        SQLITE
+    } else {
+        return None;
+    };
+
+    Some(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_offset_and_container_signatures() {
+        let mut mp4 = vec![0x00, 0x00, 0x00, 0x20];
+        mp4.extend_from_slice(b"ftypisom");
+        assert_eq!(detect_bytes(&mp4).map(|sig| sig.label), Some("mp4"));
+
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&[0x10, 0x00, 0x00, 0x00]);
+        webp.extend_from_slice(b"WEBPVP8 ");
+        assert_eq!(detect_bytes(&webp).map(|sig| sig.label), Some("webp"));
+
+        assert_eq!(
+            detect_bytes(b"SQLite format 3\0rest").map(|sig| sig.label),
+            Some("sqlite")
+        );
+        assert!(ZIP.extensions.contains(&"docx"));
+        assert_eq!(detect_bytes(b"plain text"), None);
+
+        let mut pe = vec![0_u8; 0x84];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3C..0x40].copy_from_slice(&0x80_u32.to_le_bytes());
+        assert_eq!(detect_bytes(&pe), None);
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert_eq!(detect_bytes(&pe).map(|sig| sig.label), Some("pe"));
+
+        // MPEG-1 Layer III, 128 kbit/s, 44.1 kHz; a reserved bitrate is not a frame.
+        assert_eq!(
+            detect_bytes(&[0xFF, 0xFB, 0x90, 0x64]).map(|sig| sig.label),
+            Some("mp3")
+        );
+        assert_eq!(detect_bytes(&[0xFF, 0xFB, 0xF0, 0x64]), None);
+        assert_eq!(detect_bytes(&[0xFF, 0xFB]), None);
+        assert_eq!(detect_bytes(b""), None);
+    }
+}
@@ -0,0 +1,246 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Format used by `{mtime}` / `{created}` when no explicit strftime format is given.
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Substituted for `{ext}` when the file has no extension.
+const NO_EXTENSION: &str = "noext";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ext,
+    Mtime(String),
+    Created(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Token(Token),
+}
+
+/// Splits a `targetSubfolder` template into path components. Both `/` and `\` separate,
+/// except inside a `{...}` token, whose date format may itself contain `/`.
+fn components(template: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+
+    for (idx, ch) in template.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '/' | '\\' if depth == 0 => {
+                parts.push(&template[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&template[start..]);
+
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+fn parse_component(component: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = component;
+
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err(format!("unmatched '}}' in '{}'", component));
+        }
+        if open > 0 {
+            pieces.push(Piece::Literal(rest[..open].to_string()));
+        }
+
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            return Err(format!("unclosed '{{' in '{}'", component));
+        };
+        let body = &after[..close];
+        if body.contains('{') {
+            return Err(format!("nested '{{' in '{}'", component));
+        }
+        pieces.push(Piece::Token(parse_token(body)?));
+        rest = &after[close + 1..];
+    }
+
+    if !rest.is_empty() {
+        pieces.push(Piece::Literal(rest.to_string()));
+    }
+    Ok(pieces)
+}
+
+fn parse_token(body: &str) -> Result<Token, String> {
+    let (name, format) = match body.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format)),
+        None => (body.trim(), None),
+    };
+
+    let date_format = |format: Option<&str>| -> Result<String, String> {
+        let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+        if format.is_empty() || StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("invalid date format '{}' in token '{{{}}}'", format, body));
+        }
+        Ok(format.to_string())
+    };
+
+    match name {
+        "ext" if format.is_none() => Ok(Token::Ext),
+        "mtime" => Ok(Token::Mtime(date_format(format)?)),
+        "created" => Ok(Token::Created(date_format(format)?)),
+        _ => Err(format!("unknown token '{{{}}}'", body)),
+    }
+}
+
+/// Returns human-readable problems with a `targetSubfolder` template. Empty means valid.
+///
+/// The first component must be a plain folder name: it becomes the protected top-level
+/// folder, so it cannot vary per file.
+pub fn validate(template: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    let trimmed = template.trim();
+
+    if trimmed.starts_with(['/', '\\']) || trimmed.as_bytes().get(1) == Some(&b':') {
+        errors.push(format!("'{}' must be relative to the sort root", template));
+    }
+
+    for (idx, component) in components(trimmed).into_iter().enumerate() {
+        if component == "." || component == ".." {
+            errors.push(format!("'{}' must not contain '.' or '..' components", template));
+            continue;
+        }
+
+        match parse_component(component) {
+            Ok(pieces) => {
+                if idx == 0 && pieces.iter().any(|piece| matches!(piece, Piece::Token(_))) {
+                    errors.push(format!(
+                        "'{}' must start with a plain folder name before any tokens",
+                        template
+                    ));
+                }
+            }
+            Err(err) => errors.push(format!("'{}': {}", template, err)),
+        }
+    }
+
+    errors
+}
+
+/// The static top-level folder of a template, e.g. `Images` for `Images/{mtime:%Y}`.
+pub fn top_level_folder(template: &str) -> String {
+    components(template)
+        .first()
+        .map(|folder| folder.to_string())
+        .unwrap_or_default()
+}
+
+/// Expands a template for one file into a path relative to the sort root.
+///
+/// `ext_key` is the already-normalized extension. Expanded values are sanitized so a
+/// token can never introduce a separator or a `..` component.
+pub fn expand(template: &str, path: &Path, ext_key: &str) -> Result<PathBuf, String> {
+    let mut relative = PathBuf::new();
+    let mut metadata = None;
+
+    for component in components(template) {
+        let mut expanded = String::new();
+        for piece in parse_component(component)? {
+            match piece {
+                Piece::Literal(text) => expanded.push_str(&text),
+                Piece::Token(Token::Ext) => {
+                    let value = if ext_key.is_empty() { NO_EXTENSION } else { ext_key };
+                    expanded.push_str(&sanitize(value));
+                }
+                Piece::Token(Token::Mtime(format)) => {
+                    let meta = load_metadata(path, &mut metadata)?;
+                    let time = meta.modified().map_err(|err| err.to_string())?;
+                    expanded.push_str(&sanitize(&format_time(time, &format)?));
+                }
+                Piece::Token(Token::Created(format)) => {
+                    let meta = load_metadata(path, &mut metadata)?;
+                    let time = meta
+                        .created()
+                        .or_else(|_| meta.modified())
+                        .map_err(|err| err.to_string())?;
+                    expanded.push_str(&sanitize(&format_time(time, &format)?));
+                }
+            }
+        }
+
+        let expanded = expanded.trim();
+        if expanded.is_empty() || expanded == "." || expanded == ".." {
+            return Err(format!("template '{}' expanded to an unsafe folder name", template));
+        }
+        relative.push(expanded);
+    }
+
+    Ok(relative)
+}
+
+fn load_metadata<'a>(
+    path: &Path,
+    cache: &'a mut Option<fs::Metadata>,
+) -> Result<&'a fs::Metadata, String> {
+    if cache.is_none() {
+        *cache = Some(fs::metadata(path).map_err(|err| err.to_string())?);
+    }
+    Ok(cache.as_ref().expect("metadata cached above"))
+}
+
+fn format_time(time: SystemTime, format: &str) -> Result<String, String> {
+    let local: DateTime<Local> = time.into();
+    let mut out = String::new();
+    write!(out, "{}", local.format(format))
+        .map_err(|_| format!("invalid date format '{}'", format))?;
+    Ok(out)
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            ch if ch.is_control() => '-',
+            ch => ch,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_unknown_tokens_and_traversal() {
+        assert!(validate("Images/{mtime:%Y}/{mtime:%m}").is_empty());
+        assert!(validate("Archives/{ext}").is_empty());
+        assert!(!validate("Images/{bogus}").is_empty());
+        assert!(!validate("Images/{mtime").is_empty());
+        assert!(!validate("Images/../../etc").is_empty());
+        assert!(!validate("/etc/Images").is_empty());
+        assert!(!validate("{ext}/Archives").is_empty());
+        assert_eq!(top_level_folder("Images/{mtime:%Y}"), "Images");
+    }
+
+    #[test]
+    fn expand_sanitizes_separators_from_date_formats() {
+        let path = std::env::current_exe().expect("current exe");
+        let expanded = expand("Dated/{mtime:%m/%d}", &path, "bin").expect("expand");
+        assert_eq!(expanded.components().count(), 2);
+        assert_eq!(
+            expand("Archives/{ext}", &path, "").expect("expand"),
+            PathBuf::from("Archives").join("noext")
+        );
+    }
+}
@@ -7,36 +7,39 @@ use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
-pub type DebouncedAction = Arc<dyn Fn() + Send + Sync + 'static>;
+/// Runs after the debounce window; the argument is `true` for a retry the watcher scheduled
+/// itself rather than one triggered by file events. Returning `true` asks for another pass,
+/// for files that were held back and will not produce new events.
+pub type DebouncedAction = Arc<dyn Fn(bool) -> bool + Send + Sync + 'static>;
 pub type EventObserver = Arc<dyn Fn(&Event) + Send + Sync + 'static>;
 
-#[derive(Debug)]
+/// Delay before the first retry; each further retry without new events doubles it, up to
+/// `MAX_RETRY_DELAY`, so a file that stays open does not keep the app re-walking the tree.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default)]
 pub struct WatcherController {
     pub running: bool,
     stop_tx: Option<Sender<()>>,
     handle: Option<JoinHandle<()>>,
 }
 
-impl Default for WatcherController {
-    fn default() -> Self {
-        Self {
-            running: false,
-            stop_tx: None,
-            handle: None,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WatcherStatus {
+    pub profile_id: String,
     pub running: bool,
     pub sort_root: String,
+    /// Inbox folders watched alongside the sort root while running.
+    pub inbox_folders: Vec<String>,
 }
 
+/// Watches every `(path, recursive)` pair on one notify watcher; the sort root comes first,
+/// followed by any inbox folders.
 pub fn start_watcher(
     controller: &Arc<Mutex<WatcherController>>,
-    sort_root: PathBuf,
+    paths: Vec<(PathBuf, bool)>,
     debounce: Duration,
     action: DebouncedAction,
     observer: Option<EventObserver>,
@@ -65,9 +68,16 @@ pub fn start_watcher(
             }
         };
 
-        if let Err(err) = watcher.watch(&sort_root, RecursiveMode::Recursive) {
-            let _ = startup_tx.send(Err(err));
-            return;
+        for (path, recursive) in &paths {
+            let mode = if *recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            if let Err(err) = watcher.watch(path, mode) {
+                let _ = startup_tx.send(Err(err));
+                return;
+            }
         }
 
         let _ = startup_tx.send(Ok(()));
@@ -118,6 +128,8 @@ fn run_loop(
     observer: Option<EventObserver>,
 ) {
     let mut pending_at: Option<Instant> = None;
+    // Set while the pending pass is a retry, to the delay it was scheduled with.
+    let mut retry_delay: Option<Duration> = None;
 
     loop {
         if stop_rx.try_recv().is_ok() {
@@ -132,6 +144,7 @@ fn run_loop(
 
                 if is_sorting_relevant(&event.kind) {
                     pending_at = Some(Instant::now());
+                    retry_delay = None;
                 }
             }
             Ok(Err(_)) => {}
@@ -142,7 +155,15 @@ fn run_loop(
         if let Some(started) = pending_at {
             if started.elapsed() >= debounce {
                 pending_at = None;
-                action();
+                if action(retry_delay.is_some()) {
+                    let delay =
+                        retry_delay.map_or(RETRY_DELAY, |last| (last * 2).min(MAX_RETRY_DELAY));
+                    retry_delay = Some(delay);
+                    // Schedule so that the debounce check fires `delay` from now.
+                    pending_at = Some(Instant::now() + delay.saturating_sub(debounce));
+                } else {
+                    retry_delay = None;
+                }
             }
         }
     }
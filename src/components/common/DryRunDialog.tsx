@@ -26,7 +26,7 @@ export function DryRunDialog({ plan, open, onOpenChange }: DryRunDialogProps) {
         <DialogHeader>
           <DialogTitle>Dry Run Preview</DialogTitle>
           <DialogDescription>
-            Planned moves by destination bucket. Conflicts will be renamed using the collision policy.
+            Planned moves by destination bucket. Conflicts are resolved using the collision policy.
           </DialogDescription>
         </DialogHeader>
 
@@ -57,7 +57,7 @@ export function DryRunDialog({ plan, open, onOpenChange }: DryRunDialogProps) {
               <div className="rounded-xl border border-destructive/50 bg-destructive/10 p-3 text-sm">
                 <p className="flex items-center gap-2 font-medium text-destructive">
                   <AlertTriangle className="h-4 w-4" />
-                  Rename collisions detected
+                  Collisions detected
                 </p>
               </div>
             ) : null}
@@ -72,6 +72,16 @@ export function DryRunDialog({ plan, open, onOpenChange }: DryRunDialogProps) {
                     <div key={`${entry.sourcePath}-${entry.destinationPath}`} className="rounded-lg border border-border/60 p-2 text-xs">
                       <p className="truncate text-muted-foreground">{entry.sourcePath}</p>
                       <p className="truncate">{entry.destinationPath}</p>
+                      {entry.duplicate ? (
+                        <p className="text-muted-foreground">
+                          duplicate of {entry.duplicate.original} ({entry.duplicate.action})
+                        </p>
+                      ) : null}
+                      {entry.collision ? (
+                        <p className="text-destructive">
+                          {entry.collision.action}: {entry.collision.reason}
+                        </p>
+                      ) : null}
                     </div>
                   ))}
                 </div>
@@ -6,7 +6,7 @@ import { Input } from "@/components/ui/input";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
 import { Slider } from "@/components/ui/slider";
 import { Switch } from "@/components/ui/switch";
-import type { Rules } from "@/types";
+import type { CollisionPolicy, Rules } from "@/types";
 
 interface SettingsViewProps {
   rules: Rules;
@@ -70,7 +70,7 @@ export function SettingsView({ rules, onChange, onChangeSortRoot }: SettingsView
             <p className="mb-2 text-sm">Collision policy</p>
             <Select
               value={rules.global.collisionPolicy}
-              onValueChange={(value: "rename") =>
+              onValueChange={(value: CollisionPolicy) =>
                 onChange({ ...rules, global: { ...rules.global, collisionPolicy: value } })
               }
             >
@@ -79,6 +79,11 @@ export function SettingsView({ rules, onChange, onChangeSortRoot }: SettingsView
               </SelectTrigger>
               <SelectContent>
                 <SelectItem value="rename">Rename (file (1).ext)</SelectItem>
+                <SelectItem value="skip">Skip (leave in place)</SelectItem>
+                <SelectItem value="overwriteToTrash">Overwrite (old file to Trash)</SelectItem>
+                <SelectItem value="keepNewer">Keep newer</SelectItem>
+                <SelectItem value="keepLarger">Keep larger</SelectItem>
+                <SelectItem value="dedupeIfIdentical">Trash identical duplicates</SelectItem>
               </SelectContent>
             </Select>
           </div>
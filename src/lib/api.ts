@@ -1,54 +1,130 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import type {
+  ClassificationExplanation,
+  DuplicateReport,
+  DuplicateTrashResult,
+  JournalRunSummary,
   PlanPreview,
+  ProfileSummary,
+  RecoveredRun,
+  RuleImpact,
   Rules,
+  RulesVersion,
   RunLogEvent,
   RunProgressEvent,
+  RunMode,
   RunResult,
+  UndoMode,
   UndoResult,
+  UndoSelection,
   ValidationResult,
   WatcherStatus
 } from "@/types";
 
-export function getRules() {
-  return invoke<Rules>("get_rules");
+export function listProfiles() {
+  return invoke<ProfileSummary[]>("list_profiles");
 }
 
-export function setRules(rules: Rules) {
-  return invoke<void>("set_rules", { rules });
+export function createProfile(id: string, sortRoot: string) {
+  return invoke<ProfileSummary>("create_profile", { id, sortRoot });
+}
+
+export function deleteProfile(id: string) {
+  return invoke<void>("delete_profile", { id });
+}
+
+export function getRules(profileId?: string) {
+  return invoke<Rules>("get_rules", { profileId });
+}
+
+export function setRules(rules: Rules, profileId?: string) {
+  return invoke<void>("set_rules", { rules, profileId });
+}
+
+export function listRulesVersions(profileId?: string) {
+  return invoke<RulesVersion[]>("list_rules_versions", { profileId });
+}
+
+export function restoreRulesVersion(versionId: string, profileId?: string) {
+  return invoke<Rules>("restore_rules_version", { versionId, profileId });
 }
 
 export function validateRules(rules: Rules) {
   return invoke<ValidationResult>("validate_rules", { rules });
 }
 
-export function setSortRoot(path: string) {
-  return invoke<void>("set_sort_root", { path });
+export function setSortRoot(path: string, profileId?: string, migrate?: boolean) {
+  return invoke<RunResult | null>("set_sort_root", { path, profileId, migrate });
+}
+
+export function previewSortRootRelocation(path: string, profileId?: string) {
+  return invoke<PlanPreview>("preview_sort_root_relocation", { path, profileId });
+}
+
+export function dryRun(profileId?: string, mode?: RunMode) {
+  return invoke<PlanPreview>("dry_run", { profileId, mode });
+}
+
+export function explainClassification(path: string, profileId?: string) {
+  return invoke<ClassificationExplanation>("explain_classification", { path, profileId });
+}
+
+export function previewRuleImpact(rules: Rules, profileId?: string) {
+  return invoke<RuleImpact>("preview_rule_impact", { rules, profileId });
+}
+
+export function findDuplicates(profileId?: string) {
+  return invoke<DuplicateReport>("find_duplicates", { profileId });
+}
+
+export function trashDuplicates(paths: string[], profileId?: string) {
+  return invoke<DuplicateTrashResult>("trash_duplicates", { paths, profileId });
+}
+
+export function runNow(profileId?: string, mode?: RunMode) {
+  return invoke<RunResult>("run_now", { profileId, mode });
+}
+
+export function journalRecovery(profileId?: string) {
+  return invoke<RecoveredRun[]>("journal_recovery", { profileId });
+}
+
+export function undoLastRun(profileId?: string, mode?: UndoMode) {
+  return invoke<UndoResult>("undo_last_run", { profileId, mode });
+}
+
+export function listJournalRuns(profileId?: string) {
+  return invoke<JournalRunSummary[]>("list_journal_runs", { profileId });
 }
 
-export function dryRun() {
-  return invoke<PlanPreview>("dry_run");
+export function undoRun(sessionId: string, profileId?: string, mode?: UndoMode) {
+  return invoke<UndoResult>("undo_run", { profileId, sessionId, mode });
 }
 
-export function runNow() {
-  return invoke<RunResult>("run_now");
+export function undoSelected(
+  sessionId: string,
+  selection: UndoSelection,
+  profileId?: string,
+  mode?: UndoMode
+) {
+  return invoke<UndoResult>("undo_selected", { profileId, sessionId, selection, mode });
 }
 
-export function undoLastRun() {
-  return invoke<UndoResult>("undo_last_run");
+export function redo(sessionId?: string, profileId?: string) {
+  return invoke<RunResult>("redo", { profileId, sessionId });
 }
 
-export function startWatcher() {
-  return invoke<void>("start_watcher");
+export function startWatcher(profileId?: string) {
+  return invoke<void>("start_watcher", { profileId });
 }
 
-export function stopWatcher() {
-  return invoke<void>("stop_watcher");
+export function stopWatcher(profileId?: string) {
+  return invoke<void>("stop_watcher", { profileId });
 }
 
-export function watcherStatus() {
-  return invoke<WatcherStatus>("watcher_status");
+export function watcherStatus(profileId?: string) {
+  return invoke<WatcherStatus>("watcher_status", { profileId });
 }
 
 export function onRunProgress(handler: (payload: RunProgressEvent) => void) {
@@ -1,6 +1,33 @@
-export type CollisionPolicy = "rename";
+export type CollisionPolicy =
+  | "rename"
+  | "skip"
+  | "overwriteToTrash"
+  | "keepNewer"
+  | "keepLarger"
+  | "dedupeIfIdentical";
+
+export type CollisionAction = "renamed" | "replacedExisting" | "trashedIncoming";
+
+export interface CollisionDecision {
+  policy: CollisionPolicy;
+  action: CollisionAction;
+  reason: string;
+}
+
+export type ContentSniffing = "off" | "fallback" | "override";
+
+export type DuplicateAction = "keep" | "trash" | "moveToDuplicates";
+
+export interface DuplicateMark {
+  original: string;
+  action: DuplicateAction;
+}
+
+export type RunMode = "sort" | "library";
+export type SortMode = "flatten" | "preserve" | "groupByDrop";
 
 export interface Rules {
+  schemaVersion?: number;
   global: {
     sortRoot: string;
     caseInsensitiveExt: boolean;
@@ -13,12 +40,24 @@ export interface Rules {
       minAgeSeconds: number;
       mode: "trash";
     };
+    contentSniffing?: ContentSniffing;
+    sortMode?: SortMode;
+    excludePatterns?: string[];
+    inProgressPatterns?: string[];
+    requireStableSize?: boolean;
+    skipOpenFiles?: boolean;
+    inboxFolders?: InboxFolder[];
+    duplicateDetection?: {
+      enabled: boolean;
+      action: DuplicateAction;
+    };
   };
   categories: CategoryRule[];
   misc: {
     name: string;
     targetSubfolder: string;
   };
+  bundles?: BundleRule[];
 }
 
 export interface CategoryRule {
@@ -26,6 +65,21 @@ export interface CategoryRule {
   name: string;
   targetSubfolder: string;
   extensions: string[];
+  filenameGlobs?: string[];
+  filenameRegexes?: string[];
+}
+
+export interface InboxFolder {
+  path: string;
+  recursive?: boolean;
+}
+
+export interface BundleRule {
+  id: string;
+  name: string;
+  categoryId: string;
+  markerEntries?: string[];
+  dirSuffixes?: string[];
 }
 
 export interface ValidationResult {
@@ -39,6 +93,10 @@ export interface PlanEntry {
   destinationPath: string;
   category: string;
   collisionRenamed: boolean;
+  detectedType: string | null;
+  bundle: string | null;
+  collision: CollisionDecision | null;
+  duplicate: DuplicateMark | null;
 }
 
 export interface PlanSkip {
@@ -58,8 +116,10 @@ export interface PlanPreview {
   totalCandidates: number;
   moveCount: number;
   skipCount: number;
+  pendingCount: number;
   errorCount: number;
   potentialConflicts: number;
+  duplicateCount: number;
   moves: PlanEntry[];
   skips: PlanSkip[];
   grouped: PlanGroup[];
@@ -70,14 +130,19 @@ export interface MovedFile {
   destinationPath: string;
   category: string;
   collisionRenamed: boolean;
+  bundle: string | null;
+  collision: CollisionDecision | null;
+  duplicate: DuplicateMark | null;
 }
 
 export interface RunResult {
   sessionId: string;
+  profileId?: string | null;
   startedAt: string;
   finishedAt: string;
   moved: number;
   skipped: number;
+  pending: number;
   errors: number;
   movedFiles: MovedFile[];
   skips: PlanSkip[];
@@ -93,8 +158,16 @@ export interface UndoDetail {
   message: string;
 }
 
+export type UndoMode = "restoredFolder" | "originalLocation";
+
+export interface UndoSelection {
+  paths: string[];
+  patterns: string[];
+}
+
 export interface UndoResult {
   sessionId: string | null;
+  undoSessionId: string | null;
   restored: number;
   skipped: number;
   conflicts: number;
@@ -104,11 +177,14 @@ export interface UndoResult {
 }
 
 export interface WatcherStatus {
+  profileId: string;
   running: boolean;
   sortRoot: string;
+  inboxFolders: string[];
 }
 
 export interface RunProgressEvent {
+  profileId: string | null;
   moved: number;
   skipped: number;
   errors: number;
@@ -129,3 +205,125 @@ export interface ActivityItem {
   destinationPath?: string;
   at: string;
 }
+
+export interface RuleMatch {
+  kind: "bundle" | "filenameGlob" | "filenameRegex" | "contentSignature" | "extension" | "misc";
+  categoryId: string | null;
+  categoryName: string;
+  targetSubfolder: string;
+  detail: string;
+}
+
+export interface ExtensionShadow {
+  extension: string;
+  winner: string;
+  shadowed: string[];
+}
+
+export interface ClassificationExplanation {
+  path: string;
+  extension: string;
+  detectedType: string | null;
+  matched: RuleMatch | null;
+  next: RuleMatch | null;
+  skipReason: string | null;
+  caseInsensitiveExt: boolean;
+  unknownGoesToMisc: boolean;
+  noExtensionGoesToMisc: boolean;
+  contentSniffing: ContentSniffing;
+  minFileAgeSeconds: number;
+  ageSeconds: number | null;
+  oldEnough: boolean;
+  shadowedExtensions: ExtensionShadow[];
+}
+
+export interface ReclassifiedFile {
+  path: string;
+  fromFolder: string;
+  toFolder: string;
+  targetFolder: string;
+}
+
+export interface CategoryImpact {
+  folder: string;
+  gained: number;
+  lost: number;
+}
+
+export interface RuleImpact {
+  filesExamined: number;
+  reclassified: ReclassifiedFile[];
+  categories: CategoryImpact[];
+  skips: PlanSkip[];
+}
+
+export interface DuplicateSet {
+  hash: string;
+  size: number;
+  files: string[];
+  reclaimableBytes: number;
+}
+
+export interface DuplicateReport {
+  generatedAt: string;
+  scannedFiles: number;
+  sets: DuplicateSet[];
+  reclaimableBytes: number;
+  errors: PlanSkip[];
+}
+
+export interface DuplicateTrashDetail {
+  path: string;
+  status: "trashed" | "skipped" | "error";
+  message: string;
+}
+
+export interface DuplicateTrashResult {
+  sessionId: string;
+  trashed: number;
+  skipped: number;
+  errors: number;
+  reclaimedBytes: number;
+  details: DuplicateTrashDetail[];
+}
+
+export interface OrphanedMove {
+  sourcePath: string;
+  destinationPath: string;
+  reason: string;
+}
+
+export type UndoState = "notUndone" | "partiallyUndone" | "undone" | "nothingToUndo";
+
+export interface JournalRunSummary {
+  sessionId: string;
+  createdAt: string;
+  kind: string | null;
+  targetSessionId: string | null;
+  moves: number;
+  undoable: number;
+  undone: number;
+  trashed: number;
+  redoable: number;
+  undoState: UndoState;
+}
+
+export interface RecoveredRun {
+  sessionId: string;
+  journaled: number;
+  notStarted: number;
+  orphaned: OrphanedMove[];
+  error: string | null;
+}
+
+export interface RulesVersion {
+  id: string;
+  savedAt: string;
+  sortRoot: string | null;
+}
+
+export interface ProfileSummary {
+  id: string;
+  sortRoot: string;
+  watcherRunning: boolean;
+}